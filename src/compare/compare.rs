@@ -0,0 +1,42 @@
+use anyhow::{Context, Result};
+use log::debug;
+
+use crate::exec::exec::read_repositories;
+use crate::exec::presenter::{self, Operation, Presenter};
+use crate::exec::repo_operations::RepoOperations;
+use crate::options::opts::CompareOpts;
+
+/// Starts `compare` command
+///
+/// Returns exit code of the process, non-zero if comparison failed on any of the repositories.
+///
+/// # Arguments
+///
+/// * `opts` - options from command line
+pub fn run(opts: &CompareOpts) -> Result<i32> {
+    debug!("ENTER compare run: {:?}", opts);
+
+    let mut repositories = read_repositories(&opts.source_file)?;
+
+    repositories.jobs = opts.jobs;
+    repositories.keep_going = opts.keep_going;
+
+    let mut presenter = Presenter::new(opts.format);
+
+    let result = presenter
+        .run(
+            &repositories,
+            &Operation {
+                name: "compare",
+                title: format!("commits missing between {} and {}", opts.base, opts.head),
+                field: "comparison",
+                streamed: false,
+                worktree: false,
+            },
+            |_, repo| repo.compare(&opts.base, &opts.head),
+            presenter::comparison_text,
+        )
+        .context("Failed to compare branches");
+
+    presenter.finish(result)
+}
@@ -1,29 +1,85 @@
 use anyhow::{Context, Result};
 use log::{debug, info, trace};
 
-use std::fs::File;
 use std::io;
-use std::io::{prelude::*, BufReader};
 use std::path::PathBuf;
 
 use crate::options::opts::ExecOpts;
+use crate::scan::scan_file::{Label, ScanEntry, ScanFile};
 use crate::tools::*;
 
+use super::presenter::{self, Operation, OutputFormat, Presenter};
 use super::repo::Repo;
-use super::repo_operations::RepoOperations;
+use super::repo_operations::{
+    AuthorMatcher, FetchSettings, LogRange, RepoOperations, StateFilter, UpdateStrategy,
+};
 use super::repositories::Repositories;
+use super::selector::Selector;
+
+/// Exit code returned when an operation failed on any of the repositories in `--keep-going` mode
+pub const EXIT_CODE_REPO_FAILED: i32 = 1;
 
 /// Starts `exec` command
 ///
+/// Returns exit code of the process, non-zero if an operation failed on any of the repositories.
+///
 /// # Arguments
 ///
 /// * `opts` - options from command line
-pub fn run(opts: &ExecOpts) -> Result<()> {
+pub fn run(opts: &ExecOpts) -> Result<i32> {
     debug!("ENTER exec run: {:?}", opts);
 
-    let repositories = match opts.source_file.as_ref() {
-        None => read_repositories_from_stdin()
-            .context("Failed to read repositories paths from stdin")?,
+    let mut repositories = select_repositories(&opts.source_file, &opts.group, &opts.tag)?;
+
+    repositories.jobs = opts.jobs;
+    repositories.keep_going = opts.keep_going;
+
+    let filter = get_state_filter(opts);
+
+    if !filter.is_empty() {
+        repositories
+            .retain_matching(&filter)
+            .context("Failed to check state of repositories")?;
+    }
+
+    let mut presenter = Presenter::new(opts.format);
+
+    let result = execute_operations(opts, &repositories, &mut presenter);
+
+    presenter.finish(result)
+}
+
+/// Reads repositories from the file saved by `rgit scan` or from stdin if file is not given
+///
+/// # Arguments
+///
+/// * `source_file` - `--source-file` option, default file is used if option is given without path
+pub fn read_repositories(source_file: &Option<Option<String>>) -> Result<Repositories> {
+    select_repositories(source_file, &[], &[])
+}
+
+/// Reads repositories like `read_repositories`, keeping only those which groups and tags match
+/// all selectors
+///
+/// # Arguments
+///
+/// * `source_file` - `--source-file` option, default file is used if option is given without path
+/// * `groups` - expressions matched against groups of repositories
+/// * `tags` - expressions matched against tags of repositories
+pub fn select_repositories(
+    source_file: &Option<Option<String>>,
+    groups: &[Selector],
+    tags: &[Selector],
+) -> Result<Repositories> {
+    match source_file {
+        None => {
+            if !groups.is_empty() || !tags.is_empty() {
+                anyhow::bail!(
+                    "Groups and tags are saved only in the file written by `rgit scan`, use --source-file"
+                );
+            }
+            read_repositories_from_stdin().context("Failed to read repositories paths from stdin")
+        }
         Some(path) => {
             let path = match path {
                 Some(path) => PathBuf::from(path),
@@ -32,53 +88,301 @@ pub fn run(opts: &ExecOpts) -> Result<()> {
                 }
             };
             debug!("Reading repositories from file: {}", path.to_str().unwrap());
-            read_repositories_from_file(path)
-                .context("Failed to read repositories paths from file")?
+            read_repositories_from_file(path, groups, tags)
+                .context("Failed to read repositories paths from file")
         }
-    };
+    }
+}
+
+/// Executes operations selected in command line options on all repositories
+fn execute_operations(
+    opts: &ExecOpts,
+    repositories: &Repositories,
+    presenter: &mut Presenter,
+) -> Result<()> {
+    // Commands write directly to the terminal only if their output doesn't have to be captured
+    let stream = repositories.jobs <= 1 && presenter.format == OutputFormat::Text;
 
     if opts.porcelain {
-        repositories
-            .porcelain()
+        presenter
+            .run(
+                repositories,
+                &Operation {
+                    name: "porcelain",
+                    title: String::from("git status --porcelain --branch"),
+                    field: "status",
+                    streamed: false,
+                    worktree: true,
+                },
+                |_, repo| repo.status(opts.ignored),
+                presenter::status_text,
+            )
             .context("Failed to execute porcelain")?;
     }
 
-    if opts.find_cherry_picks {
-        repositories
-            .find_cherry_picks()
+    if opts.fetch {
+        let settings = FetchSettings {
+            remotes: opts.remote.clone(),
+            prune: opts.prune,
+            tags: opts.tags,
+        };
+        // parallel workers would overwrite each other's progress line
+        let progress = presenter.format == OutputFormat::Text
+            && repositories.jobs <= 1
+            && termion::is_tty(&io::stderr());
+        let count = repositories.repos.len();
+
+        presenter
+            .run(
+                repositories,
+                &Operation {
+                    name: "fetch",
+                    title: String::from("git fetch"),
+                    field: "remotes",
+                    streamed: false,
+                    worktree: false,
+                },
+                |index, repo| match progress {
+                    true => {
+                        let result = repo.fetch_with_progress(&settings, |fetch| {
+                            presenter::print_fetch_progress(repo, index, count, fetch)
+                        });
+                        presenter::clear_progress();
+                        result
+                    }
+                    false => repo.fetch(&settings),
+                },
+                |repo, remotes| presenter::fetch_text(repo, remotes),
+            )
+            .context("Failed to fetch repositories")?;
+    }
+
+    if opts.update {
+        let strategy = match opts.rebase {
+            true => UpdateStrategy::Rebase,
+            false => UpdateStrategy::FastForward,
+        };
+
+        presenter
+            .run(
+                repositories,
+                &Operation {
+                    name: "update",
+                    title: match strategy {
+                        UpdateStrategy::FastForward => String::from("git pull --ff-only"),
+                        UpdateStrategy::Rebase => String::from("git pull --rebase"),
+                    },
+                    field: "update",
+                    streamed: false,
+                    worktree: true,
+                },
+                |_, repo| repo.update(strategy),
+                presenter::update_text,
+            )
+            .context("Failed to update repositories")?;
+    }
+
+    if opts.sync_status {
+        presenter
+            .run(
+                repositories,
+                &Operation {
+                    name: "sync-status",
+                    title: match opts.all_branches {
+                        true => String::from("branches out of sync with upstream"),
+                        false => String::from("current branches out of sync with upstream"),
+                    },
+                    field: "branches",
+                    streamed: false,
+                    worktree: false,
+                },
+                |_, repo| repo.sync_status(opts.all_branches),
+                |repo, branches| presenter::sync_status_text(repo, branches),
+            )
+            .context("Failed to compare branches with upstream")?;
+    }
+
+    if opts.submodule_status {
+        presenter
+            .run(
+                repositories,
+                &Operation {
+                    name: "submodule-status",
+                    title: String::from("submodules out of sync with superproject"),
+                    field: "submodules",
+                    streamed: false,
+                    worktree: true,
+                },
+                |_, repo| repo.submodule_status(),
+                |repo, submodules| presenter::submodule_status_text(repo, submodules),
+            )
+            .context("Failed to check submodules")?;
+    }
+
+    if opts.find_cherry_picks || opts.print_cherry_picks {
+        let with_titles = opts.print_cherry_picks;
+
+        presenter
+            .run(
+                repositories,
+                &Operation {
+                    name: match with_titles {
+                        true => "print-cherry-picks",
+                        false => "find-cherry-picks",
+                    },
+                    title: match &opts.cherry_base {
+                        Some(base) => format!("repositories with cherry-picks from {}", base),
+                        None => String::from("repositories with cherry-picks from upstream"),
+                    },
+                    field: "cherry_picks",
+                    streamed: false,
+                    worktree: false,
+                },
+                |_, repo| repo.cherry_picks(opts.cherry_base.as_deref()),
+                |repo, cherry_picks| presenter::cherry_picks_text(repo, cherry_picks, with_titles),
+            )
             .context("Failed to find cherry picks in repositories")?;
     }
 
-    if opts.print_cherry_picks {
-        repositories
-            .print_cherry_picks()
-            .context("Failed to print cherry picks in repositories")?;
+    if let Some(authors) = &opts.with_author {
+        let (authors, description) = match authors.is_empty() {
+            true => {
+                let (name, email) = (get_git_user_name()?, get_git_user_email()?);
+                let authors = AuthorMatcher::exact(&[name.clone(), email.clone()])
+                    .context("Failed to find current git user, set user.name or user.email")?;
+
+                (authors, format!("{} <{}>", name, email))
+            }
+            false => (AuthorMatcher::new(authors)?, authors.join(", ")),
+        };
+        let range = get_log_range(opts)?;
+
+        presenter
+            .run(
+                repositories,
+                &Operation {
+                    name: "with-author",
+                    title: format!("repositories with author {} {}", description, range),
+                    field: "commits",
+                    streamed: false,
+                    worktree: false,
+                },
+                |_, repo| repo.commits_with_author(&range, &authors),
+                |repo, commits| presenter::commits_text(repo, commits),
+            )
+            .context("Failed to print commits with author")?;
     }
 
-    match &opts.with_author {
-        Some(author) => {
-            let author = match author {
-                Some(author) => String::from(author),
-                None => get_git_user_name()?,
+    match get_custom_cmd_args(opts)? {
+        Some(args) => {
+            let operation = Operation {
+                name: "cmd",
+                title: format!("git {}", shell_words::join(&args)),
+                field: "output",
+                streamed: stream,
+                worktree: false,
             };
 
-            repositories
-                .print_commits_with_author(opts.number, &author)
-                .context("Failed to print commits with author")?;
+            match stream {
+                true => presenter.run(
+                    repositories,
+                    &operation,
+                    |_, repo| {
+                        presenter::print_path(repo);
+                        repo.custom_cmd_streamed(&args)
+                    },
+                    |_, _| String::new(),
+                ),
+                false => presenter.run(
+                    repositories,
+                    &operation,
+                    |_, repo| repo.custom_cmd(&args),
+                    presenter::command_text,
+                ),
+            }
+            .context("Failed to execute command on all repositories")?
         }
-        None => {}
+        None => trace!("Skipping cmd command"),
     }
 
-    match &opts.cmd {
-        Some(cmd) => repositories
-            .custom_cmd(String::from(cmd))
-            .context("Failed to execute command on all repositories")?,
-        None => trace!("Skipping cmd command"),
+    match &opts.sh {
+        Some(cmd) => {
+            let operation = Operation {
+                name: "sh",
+                title: format!("$ {}", cmd),
+                field: "output",
+                streamed: stream,
+                worktree: false,
+            };
+
+            match stream {
+                true => presenter.run(
+                    repositories,
+                    &operation,
+                    |index, repo| {
+                        presenter::print_path(repo);
+                        repo.shell_cmd_streamed(cmd, index)
+                    },
+                    |_, _| String::new(),
+                ),
+                false => presenter.run(
+                    repositories,
+                    &operation,
+                    |index, repo| repo.shell_cmd(cmd, index),
+                    presenter::command_text,
+                ),
+            }
+            .context("Failed to execute shell command on all repositories")?
+        }
+        None => trace!("Skipping sh command"),
     }
 
     Ok(())
 }
 
+/// Returns git arguments for custom command, either split from `--cmd` like a shell would do it
+/// or passed after `--` without any modifications
+fn get_custom_cmd_args(opts: &ExecOpts) -> Result<Option<Vec<String>>> {
+    if let Some(cmd) = &opts.cmd {
+        let args = shell_words::split(cmd).context(format!("Failed to parse command: {}", cmd))?;
+        return Ok(Some(args));
+    }
+
+    match opts.args.is_empty() {
+        true => Ok(None),
+        false => Ok(Some(opts.args.clone())),
+    }
+}
+
+/// Creates range of commits for log based options from command line options
+fn get_log_range(opts: &ExecOpts) -> Result<LogRange> {
+    let parse = |date: &Option<String>| -> Result<Option<i64>> {
+        match date {
+            Some(date) => Ok(Some(
+                parse_date(date).context(format!("Invalid date: {}", date))?,
+            )),
+            None => Ok(None),
+        }
+    };
+
+    Ok(LogRange {
+        number: opts.number,
+        after: parse(&opts.after)?,
+        before: parse(&opts.before)?,
+    })
+}
+
+/// Creates filter of repositories from `--only-dirty`, `--on-branch` and other state predicates
+fn get_state_filter(opts: &ExecOpts) -> StateFilter {
+    StateFilter {
+        dirty: opts.only_dirty,
+        branch: opts.on_branch.as_ref().map(|glob| glob.compile_matcher()),
+        remote: opts.remote_matches.clone(),
+        behind_upstream: opts.behind_upstream,
+        path: opts.contains_path.clone(),
+    }
+}
+
 fn read_repositories_from_stdin() -> Result<Repositories> {
     info!("Reading repository paths from stdin");
     let mut repositories = Repositories::new();
@@ -97,44 +401,118 @@ fn read_repositories_from_stdin() -> Result<Repositories> {
 
         trace!("Adding path {} to repositories", line);
 
-        match Repo::new(line) {
-            Some(repo) => repositories.repos.push(repo),
-            None => {}
+        if let Some(repo) = Repo::new(line) {
+            repositories.repos.push(repo);
         }
     }
 
     Ok(repositories)
 }
 
-fn read_repositories_from_file(path: PathBuf) -> Result<Repositories> {
+fn read_repositories_from_file(
+    path: PathBuf,
+    groups: &[Selector],
+    tags: &[Selector],
+) -> Result<Repositories> {
     info!(
         "Reading repository paths from file: {}",
         path.to_str().unwrap()
     );
     let mut repositories = Repositories::new();
 
-    let f =
-        File::open(&path).context(format!("Failed to open file: {}", path.to_str().unwrap()))?;
-    let reader = BufReader::new(f);
+    let file = ScanFile::read(&path)?;
+
+    for entry in &file.repositories {
+        if !is_selected(entry, groups, tags) {
+            trace!("Skipping repository {} not selected", entry.path.display());
+            continue;
+        }
+
+        let path = file.resolve(entry);
 
-    for line in reader.lines() {
-        match Repo::new(&line.context("Failed to read line")?) {
-            Some(repo) => repositories.repos.push(repo),
-            None => {}
+        if let Some(repo) = Repo::new(path.to_str().context("Invalid repository path")?) {
+            repositories.repos.push(repo);
         }
     }
 
     Ok(repositories)
 }
 
+fn is_selected(entry: &ScanEntry, groups: &[Selector], tags: &[Selector]) -> bool {
+    groups
+        .iter()
+        .all(|selector| selector.matches(entry.labels(Label::Group)))
+        && tags
+            .iter()
+            .all(|selector| selector.matches(entry.labels(Label::Tag)))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::scan::scan_file::ScanRoot;
+    use clap::Clap;
     use git2::Repository;
-    use std::io::LineWriter;
+    use std::io::{LineWriter, Write};
+    use std::path::Path;
     use tempfile::NamedTempFile;
     use tempfile::TempDir;
 
+    #[test]
+    fn get_custom_cmd_args_test() -> Result<()> {
+        let opts = ExecOpts::try_parse_from(vec![
+            "exec",
+            "-c",
+            r#"commit  -m 'fix build' --author="A \"B\" <a@b>""#,
+        ])?;
+        assert_eq!(
+            get_custom_cmd_args(&opts)?,
+            Some(vec![
+                String::from("commit"),
+                String::from("-m"),
+                String::from("fix build"),
+                String::from(r#"--author=A "B" <a@b>"#),
+            ])
+        );
+
+        let opts = ExecOpts::try_parse_from(vec!["exec", "--", "log", "--format=%h '%s'", "-5"])?;
+        assert_eq!(
+            get_custom_cmd_args(&opts)?,
+            Some(vec![
+                String::from("log"),
+                String::from("--format=%h '%s'"),
+                String::from("-5"),
+            ])
+        );
+
+        let opts = ExecOpts::try_parse_from(vec!["exec", "-c", "commit -m 'unterminated"])?;
+        assert!(get_custom_cmd_args(&opts).is_err());
+
+        let opts = ExecOpts::try_parse_from(vec!["exec", "--porcelain"])?;
+        assert_eq!(get_custom_cmd_args(&opts)?, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn with_author_parsing_test() -> Result<()> {
+        let opts = ExecOpts::try_parse_from(vec!["exec", "--porcelain"])?;
+        assert_eq!(opts.with_author, None);
+
+        let opts = ExecOpts::try_parse_from(vec!["exec", "--with-author", "-n", "5"])?;
+        assert_eq!(opts.with_author, Some(Vec::new()));
+        assert_eq!(opts.number, 5);
+
+        let opts =
+            ExecOpts::try_parse_from(vec!["exec", "--with-author", "^Jane", "@example.com$"])?;
+        assert_eq!(
+            opts.with_author,
+            Some(vec![String::from("^Jane"), String::from("@example.com$")])
+        );
+
+        Ok(())
+    }
+
     #[test]
     fn read_repositories_from_file_test() -> Result<()> {
         let repo_1_dir = TempDir::new()?;
@@ -155,10 +533,59 @@ mod tests {
         writer.write_all(no_repo_dir.path().to_str().unwrap().as_bytes())?;
         writer.write_all(b"\n")?;
 
-        let repositories = read_repositories_from_file(PathBuf::from(file.path()))?;
+        let repositories = read_repositories_from_file(PathBuf::from(file.path()), &[], &[])?;
 
         assert_eq!(repositories.repos.len(), 2);
 
         Ok(())
     }
+
+    #[test]
+    fn select_repositories_by_groups_and_tags() -> Result<()> {
+        let dirs = [TempDir::new()?, TempDir::new()?, TempDir::new()?];
+        let mut entries = Vec::new();
+
+        for (dir, (groups, tags)) in dirs.iter().zip([
+            (vec!["bsp"], vec!["vendor"]),
+            (vec!["bsp"], vec![]),
+            (vec!["tools"], vec![]),
+        ]) {
+            let _ = Repository::init(dir.path())?;
+            let mut entry = ScanEntry::new(dir.path());
+            entry.groups = groups.into_iter().map(String::from).collect();
+            entry.tags = tags.into_iter().map(String::from).collect();
+            entries.push(entry);
+        }
+
+        let file = NamedTempFile::new()?;
+        ScanFile::new(ScanRoot::new(Path::new("/")), entries).write(file.path())?;
+        let source_file = Some(Some(file.path().to_str().unwrap().to_string()));
+
+        let selected = |groups: &[&str], tags: &[&str]| -> Result<Vec<PathBuf>> {
+            let parse =
+                |exprs: &[&str]| exprs.iter().map(|e| e.parse()).collect::<Result<Vec<_>>>();
+            Ok(
+                select_repositories(&source_file, &parse(groups)?, &parse(tags)?)?
+                    .repos
+                    .iter()
+                    .map(|repo| PathBuf::from(repo.path()))
+                    .collect(),
+            )
+        };
+
+        assert_eq!(selected(&[], &[])?.len(), 3);
+        assert_eq!(selected(&["bsp | tools"], &[])?.len(), 3);
+        assert_eq!(
+            selected(&["bsp"], &["!vendor"])?,
+            vec![dirs[1].path().to_path_buf()]
+        );
+        assert_eq!(
+            selected(&["!bsp"], &[])?,
+            vec![dirs[2].path().to_path_buf()]
+        );
+        assert!(selected(&["bsp", "tools"], &[])?.is_empty());
+        assert!(select_repositories(&None, &["bsp".parse()?], &[]).is_err());
+
+        Ok(())
+    }
 }
@@ -1,4 +1,11 @@
+#[allow(clippy::module_inception)]
 pub mod exec;
+pub mod pool;
+pub mod presenter;
 pub mod repo;
 pub mod repo_operations;
+pub mod report;
 pub mod repositories;
+pub mod selector;
+#[cfg(test)]
+pub mod test_utils;
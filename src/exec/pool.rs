@@ -0,0 +1,170 @@
+use anyhow::Result;
+use log::trace;
+
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+
+/// Executes `operation` for every item on a pool of `jobs` worker threads
+///
+/// Results are passed to `consume` in the order of `items`, each one as soon as all previous
+/// results are consumed, so output produced by `consume` never interleaves.
+/// When `consume` returns an error no new items are started and the error is returned.
+/// With `jobs` less or equal to 1 everything is executed in the calling thread.
+///
+/// # Arguments
+///
+/// * `items` - items to execute `operation` on
+/// * `jobs` - number of worker threads
+/// * `operation` - operation executed on worker threads
+/// * `consume` - handles results of `operation` in the calling thread
+pub fn for_each_ordered<T, R, O, C>(
+    items: &[T],
+    jobs: usize,
+    operation: O,
+    mut consume: C,
+) -> Result<()>
+where
+    T: Sync,
+    R: Send,
+    O: Fn(&T) -> R + Sync,
+    C: FnMut(R) -> Result<()>,
+{
+    if jobs <= 1 || items.len() <= 1 {
+        for item in items {
+            consume(operation(item))?;
+        }
+        return Ok(());
+    }
+
+    let jobs = jobs.min(items.len());
+    trace!("Executing {} items on {} threads", items.len(), jobs);
+
+    let next = AtomicUsize::new(0);
+    let stop = AtomicBool::new(false);
+    let (sender, receiver) = mpsc::channel();
+
+    thread::scope(|scope| {
+        let (next, stop, operation) = (&next, &stop, &operation);
+
+        for _ in 0..jobs {
+            let sender = sender.clone();
+
+            scope.spawn(move || loop {
+                if stop.load(Ordering::SeqCst) {
+                    break;
+                }
+
+                let index = next.fetch_add(1, Ordering::SeqCst);
+                if index >= items.len() {
+                    break;
+                }
+
+                if sender.send((index, operation(&items[index]))).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(sender);
+
+        let mut pending = BTreeMap::new();
+        let mut expected = 0;
+
+        for (index, result) in receiver {
+            pending.insert(index, result);
+
+            while let Some(result) = pending.remove(&expected) {
+                expected += 1;
+
+                if let Err(err) = consume(result) {
+                    stop.store(true, Ordering::SeqCst);
+                    return Err(err);
+                }
+            }
+        }
+
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::bail;
+    use std::time::Duration;
+
+    #[test]
+    fn for_each_ordered_keeps_order() -> Result<()> {
+        let items: Vec<u64> = (0..20).collect();
+        let mut results = Vec::new();
+
+        for_each_ordered(
+            &items,
+            4,
+            |&item| {
+                thread::sleep(Duration::from_millis((20 - item) % 7));
+                item * 2
+            },
+            |result| {
+                results.push(result);
+                Ok(())
+            },
+        )?;
+
+        assert_eq!(
+            results,
+            items.iter().map(|item| item * 2).collect::<Vec<_>>()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn for_each_ordered_stops_on_error() {
+        let items: Vec<u64> = (0..100).collect();
+        let executed = AtomicUsize::new(0);
+        let mut consumed = Vec::new();
+
+        let res = for_each_ordered(
+            &items,
+            2,
+            |&item| {
+                executed.fetch_add(1, Ordering::SeqCst);
+                thread::sleep(Duration::from_millis(1));
+                item
+            },
+            |result| {
+                if result == 3 {
+                    bail!("Failed on {}", result);
+                }
+                consumed.push(result);
+                Ok(())
+            },
+        );
+
+        assert!(res.is_err());
+        assert_eq!(consumed, vec![0, 1, 2]);
+        assert!(executed.load(Ordering::SeqCst) < items.len());
+    }
+
+    #[test]
+    fn for_each_ordered_single_job() -> Result<()> {
+        let items = vec!["a", "b", "c"];
+        let mut results = Vec::new();
+
+        for_each_ordered(
+            &items,
+            1,
+            |item| item.to_uppercase(),
+            |result| {
+                results.push(result);
+                Ok(())
+            },
+        )?;
+
+        assert_eq!(results, vec!["A", "B", "C"]);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,939 @@
+use anyhow::{Context, Result};
+use chrono::{Local, TimeZone};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use termion::color;
+
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use super::exec::EXIT_CODE_REPO_FAILED;
+use super::repo::Repo;
+use super::report::{
+    BranchSync, CherryPick, CommandFailed, CommandOutput, CommitMatch, Comparison, Detection,
+    FetchProgress, GrepMatch, LogEntry, RemoteFetch, RepoKind, StatusReport, SubmoduleState,
+    UpdateOutcome, UpdateReport,
+};
+use super::repositories::Repositories;
+use crate::tools::format_relative_time;
+
+/// Format in which results of operations are printed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+    /// Human readable, coloured text
+    Text,
+    /// Single JSON array with one record per repository and operation
+    Json,
+    /// One JSON record per line, printed as soon as the repository is processed
+    Ndjson,
+}
+
+impl FromStr for OutputFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(format: &str) -> Result<Self> {
+        match format {
+            "text" => Ok(OutputFormat::Text),
+            "json" => Ok(OutputFormat::Json),
+            "ndjson" => Ok(OutputFormat::Ndjson),
+            _ => anyhow::bail!("Unknown output format: {}", format),
+        }
+    }
+}
+
+/// Describes operation which failed on a single repository
+#[derive(Debug, Clone, PartialEq)]
+pub struct RepoFailure {
+    /// Path to the repository
+    pub path: PathBuf,
+    /// Title of the failed operation
+    pub operation: String,
+    /// Exit code of the executed command, `None` if no command exited with non-zero status
+    pub exit_code: Option<i32>,
+    /// Error message
+    pub error: String,
+}
+
+/// Describes operation which results are presented
+pub struct Operation<'a> {
+    /// Name of the operation used in records, e.g. `porcelain`
+    pub name: &'a str,
+    /// Title printed before results in `text` format
+    pub title: String,
+    /// Key under which result is placed in records if it's not a struct, e.g. `commits`
+    pub field: &'a str,
+    /// Output of the operation goes directly to the terminal, so it's not printed again on failure
+    pub streamed: bool,
+    /// Operation needs a working tree, bare repositories are skipped
+    pub worktree: bool,
+}
+
+/// Presents results of operations in selected format and collects failures
+pub struct Presenter {
+    /// Format in which results are printed
+    pub format: OutputFormat,
+    records: Vec<Value>,
+    failures: Vec<RepoFailure>,
+}
+
+impl Presenter {
+    /// Creates new instance of `Presenter`
+    pub fn new(format: OutputFormat) -> Presenter {
+        Presenter {
+            format,
+            records: Vec::new(),
+            failures: Vec::new(),
+        }
+    }
+    /// Returns operations which failed on repositories
+    pub fn failures(&self) -> &[RepoFailure] {
+        &self.failures
+    }
+    /// Executes `execute` on all `repositories` and presents every result as soon as it is available
+    ///
+    /// Bare repositories are skipped if the operation needs a working tree.
+    ///
+    /// # Arguments
+    ///
+    /// * `repositories` - repositories to execute operation on
+    /// * `operation` - presented operation
+    /// * `execute` - executed for every repository together with its index
+    /// * `text` - renders result as text printed in `text` format, empty if there is nothing to print
+    pub fn run<T, F, P>(
+        &mut self,
+        repositories: &Repositories,
+        operation: &Operation,
+        execute: F,
+        text: P,
+    ) -> Result<()>
+    where
+        T: Serialize + Send,
+        F: Fn(usize, &Repo) -> Result<T> + Sync,
+        P: Fn(&Repo, &T) -> String,
+    {
+        if self.format == OutputFormat::Text {
+            print_title(&operation.title);
+        }
+
+        repositories.execute(
+            |index, repo| match operation.worktree && repo.kind()? == RepoKind::Bare {
+                true => Ok(None),
+                false => execute(index, repo).map(Some),
+            },
+            |repo, result| match result {
+                Ok(Some(result)) => self.present_success(operation, repo, result, &text),
+                Ok(None) => {
+                    self.present_skipped(operation, repo, "bare repository without a working tree")
+                }
+                Err(err) => self.present_failure(operation, repo, err),
+            },
+        )?;
+
+        Ok(())
+    }
+    /// Presents result of an operation executed on a single repository
+    ///
+    /// Failed commands are presented together with their output.
+    ///
+    /// # Arguments
+    ///
+    /// * `operation` - presented operation
+    /// * `repo` - repository on which operation was executed
+    /// * `result` - result of the operation
+    /// * `text` - renders result as text printed in `text` format
+    pub fn present<T: Serialize>(
+        &mut self,
+        operation: &Operation,
+        repo: &Repo,
+        result: &Result<T>,
+        text: &dyn Fn(&Repo, &T) -> String,
+    ) -> Result<()> {
+        match result {
+            Ok(result) => self.present_success(operation, repo, result, text),
+            Err(err) => self.present_failure(operation, repo, err),
+        }
+    }
+    fn present_success<T: Serialize>(
+        &mut self,
+        operation: &Operation,
+        repo: &Repo,
+        result: &T,
+        text: &dyn Fn(&Repo, &T) -> String,
+    ) -> Result<()> {
+        let text = text(repo, result);
+        self.print(operation, repo, Some(result), None, text)?;
+
+        io::stdout().flush().context("Failed to flush stdout")
+    }
+    fn present_failure(
+        &mut self,
+        operation: &Operation,
+        repo: &Repo,
+        err: &anyhow::Error,
+    ) -> Result<()> {
+        let failed = err.downcast_ref::<CommandFailed>();
+
+        let text = match (failed, operation.streamed) {
+            (Some(failed), false) => command_text(repo, &failed.output),
+            _ => String::new(),
+        };
+
+        self.print(
+            operation,
+            repo,
+            failed.map(|failed| &failed.output),
+            Some(format!("{:#}", err)),
+            text,
+        )?;
+
+        self.failures.push(RepoFailure {
+            path: repo.path().to_path_buf(),
+            operation: operation.title.clone(),
+            exit_code: failed.and_then(|failed| failed.output.exit_code),
+            error: format!("{:#}", err),
+        });
+
+        io::stdout().flush().context("Failed to flush stdout")
+    }
+    /// Presents repository on which operation wasn't executed, it's not treated as a failure
+    ///
+    /// Records of skipped repositories contain `skipped` with the `reason`.
+    fn present_skipped(&mut self, operation: &Operation, repo: &Repo, reason: &str) -> Result<()> {
+        let mut record = record::<()>(repo.path(), operation, None, None)?;
+        record["skipped"] = Value::from(reason);
+
+        match self.format {
+            OutputFormat::Text => print!("{}skipped, {}\n\n", format_path(repo), reason),
+            OutputFormat::Json => self.records.push(record),
+            OutputFormat::Ndjson => println!("{}", record),
+        }
+
+        io::stdout().flush().context("Failed to flush stdout")
+    }
+    fn print<T: Serialize>(
+        &mut self,
+        operation: &Operation,
+        repo: &Repo,
+        result: Option<&T>,
+        error: Option<String>,
+        text: String,
+    ) -> Result<()> {
+        match self.format {
+            OutputFormat::Text => print!("{}", text),
+            OutputFormat::Json => {
+                let record = record(repo.path(), operation, result, error)?;
+                self.records.push(record);
+            }
+            OutputFormat::Ndjson => {
+                println!("{}", record(repo.path(), operation, result, error)?)
+            }
+        }
+
+        Ok(())
+    }
+    /// Presents single entry of results which are not grouped by repositories, e.g. unified log
+    ///
+    /// # Arguments
+    ///
+    /// * `entry` - presented entry, becomes a record in `json` and `ndjson` formats
+    /// * `text` - entry rendered as text printed in `text` format
+    pub fn present_entry<T: Serialize>(&mut self, entry: &T, text: String) -> Result<()> {
+        match self.format {
+            OutputFormat::Text => print!("{}", text),
+            OutputFormat::Json => self
+                .records
+                .push(serde_json::to_value(entry).context("Failed to serialize entry")?),
+            OutputFormat::Ndjson => println!(
+                "{}",
+                serde_json::to_string(entry).context("Failed to serialize entry")?
+            ),
+        }
+
+        Ok(())
+    }
+    /// Prints all records collected so far as a JSON array when working in `json` format
+    pub fn print_records(&mut self) -> Result<()> {
+        if self.format != OutputFormat::Json {
+            return Ok(());
+        }
+
+        let records = std::mem::take(&mut self.records);
+
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&records).context("Failed to serialize records")?
+        );
+
+        Ok(())
+    }
+    /// Prints collected records and summary of failures after all operations finished
+    ///
+    /// Returns exit code of the process, non-zero if an operation failed on any of the repositories,
+    /// or error returned by operations.
+    ///
+    /// # Arguments
+    ///
+    /// * `result` - result of executing all operations
+    pub fn finish(&mut self, result: Result<()>) -> Result<i32> {
+        self.print_records().context("Failed to print records")?;
+        result?;
+
+        self.print_failures();
+
+        match self.failures.is_empty() {
+            true => Ok(0),
+            false => Ok(EXIT_CODE_REPO_FAILED),
+        }
+    }
+    /// Pretty prints summary of failed operations, doesn't print anything if there are no failures
+    ///
+    /// Failures are part of the records in `json` and `ndjson` formats, so the summary is printed
+    /// only in `text` format.
+    pub fn print_failures(&self) {
+        if self.failures.is_empty() || self.format != OutputFormat::Text {
+            return;
+        }
+
+        print_title(&format!("{} failed operations", self.failures.len()));
+
+        let rows: Vec<[String; 4]> = self
+            .failures
+            .iter()
+            .map(|failure| {
+                [
+                    failure.path.display().to_string(),
+                    failure.operation.clone(),
+                    failure
+                        .exit_code
+                        .map_or_else(|| String::from("-"), |code| code.to_string()),
+                    failure.error.clone(),
+                ]
+            })
+            .collect();
+
+        let header = [
+            String::from("REPOSITORY"),
+            String::from("OPERATION"),
+            String::from("EXIT CODE"),
+            String::from("ERROR"),
+        ];
+
+        let mut widths = [0; 3];
+        for row in rows.iter().chain(std::iter::once(&header)) {
+            for (width, column) in widths.iter_mut().zip(row.iter()) {
+                *width = (*width).max(column.chars().count());
+            }
+        }
+
+        for row in std::iter::once(&header).chain(rows.iter()) {
+            println!(
+                "{:w0$}  {:w1$}  {:w2$}  {}",
+                row[0],
+                row[1],
+                row[2],
+                row[3],
+                w0 = widths[0],
+                w1 = widths[1],
+                w2 = widths[2]
+            );
+        }
+    }
+}
+
+/// Creates JSON record describing result of an operation executed on a single repository
+///
+/// Fields of `data` are placed directly in the record next to `path`, `operation`, `success` and `error`,
+/// data which is not a struct is placed under `operation.field` key.
+///
+/// # Arguments
+///
+/// * `path` - path to the repository
+/// * `operation` - executed operation
+/// * `data` - operation specific result
+/// * `error` - error message if operation failed
+pub fn record<T: Serialize>(
+    path: &Path,
+    operation: &Operation,
+    data: Option<&T>,
+    error: Option<String>,
+) -> Result<Value> {
+    let mut record = Map::new();
+
+    record.insert(String::from("path"), Value::from(path.to_string_lossy()));
+    record.insert(String::from("operation"), Value::from(operation.name));
+    record.insert(String::from("success"), Value::from(error.is_none()));
+    record.insert(
+        String::from("error"),
+        error.map_or(Value::Null, Value::from),
+    );
+
+    if let Some(data) = data {
+        match serde_json::to_value(data).context("Failed to serialize result")? {
+            Value::Object(data) => record.extend(data),
+            data => {
+                record.insert(String::from(operation.field), data);
+            }
+        }
+    }
+
+    Ok(Value::Object(record))
+}
+
+/// Pretty prints title of executed command
+pub fn print_title(title: &str) {
+    print!(
+        "{}\n{}{}{}\n",
+        color::Fg(color::Red),
+        title,
+        color::Reset.fg_str(),
+        color::Reset.bg_str()
+    );
+}
+
+/// Renders path of the repository printed before its results
+pub fn format_path(repo: &Repo) -> String {
+    format!(
+        "{}{}\n{}{}{}\n",
+        color::Bg(color::Rgb(32, 32, 32)),
+        color::Fg(color::Blue),
+        repo.path().to_str().unwrap(),
+        color::Reset.fg_str(),
+        color::Reset.bg_str()
+    )
+}
+
+/// Pretty prints path of the repository
+pub fn print_path(repo: &Repo) {
+    print!("{}", format_path(repo));
+}
+
+/// Renders output of a command executed on a repository
+pub fn command_text(repo: &Repo, output: &CommandOutput) -> String {
+    format!("{}{}{}", format_path(repo), output.stdout, output.stderr)
+}
+
+/// Renders status similar to `git status --porcelain --branch`, empty if no files are reported
+pub fn status_text(repo: &Repo, status: &StatusReport) -> String {
+    if status.entries.is_empty() {
+        return String::new();
+    }
+
+    let mut text = format_path(repo);
+    text.push_str(&format!("## {}\n", branch_text(status)));
+
+    for entry in &status.entries {
+        text.push_str(&format!("{} {}\n", entry.status, entry.path));
+    }
+    text.push('\n');
+
+    text
+}
+
+/// Renders branch line of `git status --porcelain --branch`, e.g. `master...origin/master [ahead 1]`
+fn branch_text(status: &StatusReport) -> String {
+    let mut text = match &status.branch {
+        Some(branch) => branch.clone(),
+        None => String::from("HEAD (no branch)"),
+    };
+
+    if let Some(upstream) = &status.upstream {
+        text.push_str(&format!(
+            "...{}{}",
+            upstream,
+            distance_text(status.ahead, status.behind)
+        ));
+    }
+
+    text
+}
+
+/// Renders distance to upstream like git does, e.g. ` [ahead 1, behind 2]`, empty if there is no distance
+fn distance_text(ahead: usize, behind: usize) -> String {
+    match (ahead, behind) {
+        (0, 0) => String::new(),
+        (ahead, 0) => format!(" [ahead {}]", ahead),
+        (0, behind) => format!(" [behind {}]", behind),
+        (ahead, behind) => format!(" [ahead {}, behind {}]", ahead, behind),
+    }
+}
+
+/// Renders branches which are out of sync with their upstream, empty if all branches are in sync
+pub fn sync_status_text(repo: &Repo, branches: &[BranchSync]) -> String {
+    let mut text = String::new();
+
+    for branch in branches.iter().filter(|branch| !branch.is_in_sync()) {
+        text.push_str(&format!(
+            "## {}...{}{}\n",
+            branch.branch,
+            branch.upstream,
+            distance_text(branch.ahead, branch.behind)
+        ));
+    }
+
+    match text.is_empty() {
+        true => text,
+        false => format!("{}{}\n", format_path(repo), text),
+    }
+}
+
+/// Renders submodules which aren't initialized, are checked out at a different commit than recorded
+/// in the superproject or have changes, nothing if all submodules are clean
+pub fn submodule_status_text(repo: &Repo, submodules: &[SubmoduleState]) -> String {
+    let mut text = String::new();
+
+    for submodule in submodules.iter().filter(|submodule| !submodule.is_clean()) {
+        let mut states = Vec::new();
+
+        if submodule.uninitialized {
+            states.push(String::from("not initialized"));
+        } else if submodule.recorded != submodule.checked_out {
+            states.push(format!(
+                "checked out {}, recorded {}",
+                submodule.checked_out.as_deref().unwrap_or("nothing"),
+                submodule.recorded.as_deref().unwrap_or("nothing")
+            ));
+        }
+        if submodule.dirty {
+            states.push(String::from("dirty"));
+        }
+
+        text.push_str(&format!("{}: {}\n", submodule.path, states.join(", ")));
+    }
+
+    match text.is_empty() {
+        true => text,
+        false => format!("{}{}\n", format_path(repo), text),
+    }
+}
+
+/// Renders number of bytes in human readable form, e.g. `1.50 KiB`
+fn format_bytes(bytes: usize) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+
+    if bytes < 1024 {
+        return format!("{} bytes", bytes);
+    }
+
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    format!("{:.2} {}", size, UNITS[unit])
+}
+
+/// Prints progress of fetching a repository in a single line of stderr, overwriting previous progress
+///
+/// # Arguments
+///
+/// * `repo` - fetched repository
+/// * `index` - index of the repository
+/// * `count` - number of all fetched repositories
+/// * `progress` - progress of fetching a remote
+pub fn print_fetch_progress(repo: &Repo, index: usize, count: usize, progress: &FetchProgress) {
+    eprint!(
+        "\r{}[{}/{}] {} {}: objects {}/{}, {}",
+        termion::clear::CurrentLine,
+        index + 1,
+        count,
+        repo.path().display(),
+        progress.remote,
+        progress.received_objects,
+        progress.total_objects,
+        format_bytes(progress.received_bytes)
+    );
+}
+
+/// Clears line with progress printed by `print_fetch_progress`
+pub fn clear_progress() {
+    eprint!("\r{}", termion::clear::CurrentLine);
+}
+
+/// Renders references updated by fetch similar to `git fetch`, empty if nothing was updated
+pub fn fetch_text(repo: &Repo, remotes: &[RemoteFetch]) -> String {
+    let mut text = String::new();
+
+    for remote in remotes
+        .iter()
+        .filter(|remote| !remote.updated_refs.is_empty())
+    {
+        text.push_str(&format!(
+            "From {}: received {}/{} objects, {}\n",
+            remote.remote,
+            remote.received_objects,
+            remote.total_objects,
+            format_bytes(remote.received_bytes)
+        ));
+
+        for update in &remote.updated_refs {
+            let name = update
+                .name
+                .strip_prefix("refs/remotes/")
+                .or_else(|| update.name.strip_prefix("refs/"))
+                .unwrap_or(&update.name);
+
+            let change = match (&update.old, &update.new) {
+                (Some(old), Some(new)) => format!("   {}..{}", old, new),
+                (None, _) => String::from(" * [new]"),
+                (_, None) => String::from(" - [deleted]"),
+            };
+
+            text.push_str(&format!("{:19}  {}\n", change, name));
+        }
+    }
+
+    match text.is_empty() {
+        true => text,
+        false => format!("{}{}\n", format_path(repo), text),
+    }
+}
+
+/// Renders updated and refused branches, empty if branch didn't need any update
+pub fn update_text(repo: &Repo, update: &UpdateReport) -> String {
+    let branch = format!(
+        "{}...{}",
+        update.branch.as_deref().unwrap_or_default(),
+        update.upstream.as_deref().unwrap_or_default()
+    );
+
+    let text = match (update.outcome, &update.old, &update.new) {
+        (UpdateOutcome::FastForwarded, Some(old), Some(new)) => {
+            format!("{}: fast-forwarded {}..{}", branch, old, new)
+        }
+        (UpdateOutcome::Rebased, Some(old), Some(new)) => format!(
+            "{}: rebased {} commits {}..{}",
+            branch, update.ahead, old, new
+        ),
+        (UpdateOutcome::Dirty, _, _) => format!(
+            "{}{}: refused, working tree has changes",
+            branch,
+            distance_text(update.ahead, update.behind)
+        ),
+        (UpdateOutcome::Diverged, _, _) => format!(
+            "{}{}: refused, branches have diverged, use --rebase",
+            branch,
+            distance_text(update.ahead, update.behind)
+        ),
+        (UpdateOutcome::Conflicts, _, _) => format!(
+            "{}{}: rebase aborted because of conflicts",
+            branch,
+            distance_text(update.ahead, update.behind)
+        ),
+        _ => return String::new(),
+    };
+
+    format!("{}{}\n\n", format_path(repo), text)
+}
+
+/// Renders commits missing on compared branches, empty if both branches contain the same commits
+pub fn comparison_text(repo: &Repo, comparison: &Comparison) -> String {
+    let mut text = String::new();
+
+    for branch in &comparison.missing_branches {
+        text.push_str(&format!("branch {} doesn't exist\n", branch));
+    }
+
+    let now = Local::now().timestamp();
+
+    for (branch, commits) in &[
+        (&comparison.base, &comparison.missing_in_base),
+        (&comparison.head, &comparison.missing_in_head),
+    ] {
+        if commits.is_empty() {
+            continue;
+        }
+
+        text.push_str(&format!("missing in {} ({}):\n", branch, commits.len()));
+
+        for commit in commits.iter() {
+            text.push_str(&format!(
+                "* {} {} ({}) <{}>\n",
+                commit.commit,
+                commit.summary,
+                format_relative_time(commit.time, now),
+                commit.author
+            ));
+        }
+    }
+
+    match text.is_empty() {
+        true => text,
+        false => format!("{}{}\n", format_path(repo), text),
+    }
+}
+
+/// Renders matching lines of tracked files prefixed with path to the repository,
+/// e.g. `meta-foo/conf/layer.conf:3:BBFILE_COLLECTIONS += "foo"`
+pub fn grep_text(repo: &Repo, matches: &[GrepMatch]) -> String {
+    matches
+        .iter()
+        .map(|found| {
+            format!(
+                "{}{}{}:{}{}{}:{}\n",
+                color::Fg(color::Magenta),
+                repo.path().join(&found.path).display(),
+                color::Reset.fg_str(),
+                color::Fg(color::Green),
+                found.line,
+                color::Reset.fg_str(),
+                found.text
+            )
+        })
+        .collect()
+}
+
+/// Renders commit of unified log, e.g. `2026-10-16 14:03 meta-foo 1234567 Fix build <Jane Doe>`
+pub fn log_entry_text(entry: &LogEntry) -> String {
+    format!(
+        "{} {}{}{} {} {} <{}>\n",
+        Local
+            .timestamp(entry.commit.time, 0)
+            .format("%Y-%m-%d %H:%M"),
+        color::Fg(color::Blue),
+        entry.repository,
+        color::Reset.fg_str(),
+        entry.commit.commit,
+        entry.commit.summary,
+        entry.commit.author
+    )
+}
+
+/// Renders cherry-picks, empty if there are no cherry-picks
+///
+/// # Arguments
+///
+/// * `with_titles` - include cherry-picks, not only repository path
+pub fn cherry_picks_text(repo: &Repo, cherry_picks: &[CherryPick], with_titles: bool) -> String {
+    if cherry_picks.is_empty() {
+        return String::new();
+    }
+
+    let mut text = format_path(repo);
+
+    if with_titles {
+        for cherry_pick in cherry_picks {
+            text.push_str(&format!(
+                "{} {} (= {}, {})\n",
+                cherry_pick.commit,
+                cherry_pick.summary,
+                cherry_pick.equivalent,
+                match cherry_pick.detected_by {
+                    Detection::Trailer => "trailer",
+                    Detection::PatchId => "patch-id",
+                }
+            ));
+        }
+    }
+
+    text
+}
+
+/// Renders commits similar to `git log --pretty="%h -%d %s (%cr) <%an>"`, empty if there are no commits
+pub fn commits_text(repo: &Repo, commits: &[CommitMatch]) -> String {
+    if commits.is_empty() {
+        return String::new();
+    }
+
+    let now = Local::now().timestamp();
+    let mut text = format_path(repo);
+
+    for commit in commits {
+        let refs = match commit.refs.is_empty() {
+            true => String::new(),
+            false => format!(" ({})", commit.refs),
+        };
+
+        text.push_str(&format!(
+            "* {} -{} {} ({}) <{}>\n",
+            commit.commit,
+            refs,
+            commit.summary,
+            format_relative_time(commit.time, now),
+            commit.author
+        ));
+    }
+
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exec::repo_operations::RepoOperations;
+    use crate::exec::report::{FileState, StatusEntry};
+
+    fn operation(name: &'static str, field: &'static str) -> Operation<'static> {
+        Operation {
+            name,
+            title: String::from(name),
+            field,
+            streamed: false,
+            worktree: false,
+        }
+    }
+
+    #[test]
+    fn record_flattens_data() -> Result<()> {
+        let status = StatusReport {
+            branch: Some(String::from("master")),
+            entries: vec![StatusEntry {
+                status: String::from("??"),
+                path: String::from("file"),
+                state: FileState::Untracked,
+                staged: None,
+                unstaged: None,
+            }],
+            ..Default::default()
+        };
+
+        let record = record(
+            Path::new("/repo"),
+            &operation("porcelain", "status"),
+            Some(&status),
+            None,
+        )?;
+
+        assert_eq!(
+            record,
+            serde_json::json!({
+                "path": "/repo",
+                "operation": "porcelain",
+                "success": true,
+                "error": null,
+                "branch": "master",
+                "upstream": null,
+                "ahead": 0,
+                "behind": 0,
+                "entries": [{
+                    "status": "??",
+                    "path": "file",
+                    "state": "untracked",
+                    "staged": null,
+                    "unstaged": null,
+                }],
+            })
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn record_places_list_under_field() -> Result<()> {
+        let commits: Vec<CommitMatch> = Vec::new();
+
+        let record = record(
+            Path::new("/repo"),
+            &operation("with-author", "commits"),
+            Some(&commits),
+            None,
+        )?;
+
+        assert_eq!(record["commits"], serde_json::json!([]));
+
+        Ok(())
+    }
+
+    #[test]
+    fn record_with_error() -> Result<()> {
+        let record = record::<CommandOutput>(
+            Path::new("/repo"),
+            &operation("cmd", "output"),
+            None,
+            Some(String::from("Failed")),
+        )?;
+
+        assert_eq!(record["success"], Value::from(false));
+        assert_eq!(record["error"], Value::from("Failed"));
+        assert!(record.get("stdout").is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn run_skips_bare_repositories_for_worktree_operations() -> Result<()> {
+        let repo_dir = tempfile::TempDir::new()?;
+        let _ = git2::Repository::init_bare(repo_dir.path())?;
+
+        let mut repositories = Repositories::new();
+        repositories.keep_going = true;
+        repositories
+            .repos
+            .push(Repo::new(repo_dir.path().to_str().unwrap()).unwrap());
+
+        let mut presenter = Presenter::new(OutputFormat::Json);
+        let mut porcelain = operation("porcelain", "status");
+        porcelain.worktree = true;
+
+        presenter.run(
+            &repositories,
+            &porcelain,
+            |_, repo| repo.status(false),
+            status_text,
+        )?;
+        assert!(presenter.failures().is_empty());
+        assert_eq!(presenter.records[0]["success"], Value::from(true));
+        assert_eq!(
+            presenter.records[0]["skipped"],
+            Value::from("bare repository without a working tree")
+        );
+
+        porcelain.worktree = false;
+        presenter.run(
+            &repositories,
+            &porcelain,
+            |_, repo| repo.status(false),
+            status_text,
+        )?;
+        assert_eq!(presenter.failures().len(), 1);
+        assert!(presenter.failures()[0].error.contains("bare repository"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn output_format_from_str() {
+        assert_eq!(OutputFormat::from_str("text").unwrap(), OutputFormat::Text);
+        assert_eq!(OutputFormat::from_str("json").unwrap(), OutputFormat::Json);
+        assert_eq!(
+            OutputFormat::from_str("ndjson").unwrap(),
+            OutputFormat::Ndjson
+        );
+        assert!(OutputFormat::from_str("xml").is_err());
+    }
+
+    #[test]
+    fn branch_text_test() {
+        let mut status = StatusReport {
+            branch: Some(String::from("master")),
+            ..Default::default()
+        };
+        assert_eq!(branch_text(&status), "master");
+
+        status.upstream = Some(String::from("origin/master"));
+        assert_eq!(branch_text(&status), "master...origin/master");
+
+        status.ahead = 2;
+        assert_eq!(branch_text(&status), "master...origin/master [ahead 2]");
+
+        status.behind = 1;
+        assert_eq!(
+            branch_text(&status),
+            "master...origin/master [ahead 2, behind 1]"
+        );
+
+        let status = StatusReport::default();
+        assert_eq!(branch_text(&status), "HEAD (no branch)");
+    }
+
+    #[test]
+    fn format_bytes_test() {
+        assert_eq!(format_bytes(0), "0 bytes");
+        assert_eq!(format_bytes(1023), "1023 bytes");
+        assert_eq!(format_bytes(1536), "1.50 KiB");
+        assert_eq!(format_bytes(5 * 1024 * 1024), "5.00 MiB");
+    }
+}
@@ -1,12 +1,31 @@
 use anyhow::{Context, Result};
-use git2::Repository;
+use git2::{
+    build::CheckoutBuilder, AutotagOption, BranchType, Commit, Cred, CredentialType, DiffDelta,
+    DiffOptions, ErrorCode, FetchOptions, FetchPrune, Mailmap, ObjectType, Oid, Pathspec,
+    PathspecFlags, Reference, RemoteCallbacks, Repository, Sort, StatusOptions, SubmoduleIgnore,
+    TreeWalkMode, TreeWalkResult,
+};
 use log::{trace, warn};
-use termion::color;
+use regex::Regex;
 
-use std::path::PathBuf;
-use std::process::Command;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus};
 
-use super::repo_operations::RepoOperations;
+use super::repo_operations::{
+    AuthorMatcher, FetchSettings, FetchTags, GrepSettings, HistoryFilter, LogRange, RepoOperations,
+    StateFilter, UpdateStrategy,
+};
+use super::report::{
+    BranchSync, Change, CherryPick, CommandFailed, CommandOutput, CommitMatch, CommitSummary,
+    Comparison, Detection, FetchProgress, FileState, GrepMatch, RefUpdate, RemoteFetch, RepoKind,
+    StatusEntry, StatusReport, SubmoduleState, UpdateOutcome, UpdateReport,
+};
+
+/// Mode of index entries describing submodules
+const FILE_MODE_COMMIT: u32 = 0o160000;
+/// Mode of index entries describing symbolic links
+const FILE_MODE_LINK: u32 = 0o120000;
 
 /// Struct describing single repository
 pub struct Repo {
@@ -45,164 +64,2259 @@ impl Repo {
         }
     }
 
-    fn print_path(&self) {
-        print!(
-            "{}{}\n{}{}{}\n",
-            color::Bg(color::Rgb(32, 32, 32)),
-            color::Fg(color::Blue),
-            self.path.to_str().unwrap(),
-            color::Reset.fg_str(),
-            color::Reset.bg_str()
+    /// Opens the repository with libgit2
+    fn open(&self) -> Result<Repository> {
+        Repository::open(&self.path).context(format!(
+            "Failed to open repository: {}",
+            self.path.display()
+        ))
+    }
+
+    /// Opens the repository with libgit2, fails if the repository is bare
+    fn open_worktree(&self) -> Result<Repository> {
+        let repository = self.open()?;
+
+        if repository.is_bare() {
+            anyhow::bail!(
+                "{} is a bare repository without a working tree",
+                self.path.display()
+            );
+        }
+
+        Ok(repository)
+    }
+
+    /// Returns kind of the repository, e.g. bare repository
+    pub fn kind(&self) -> Result<RepoKind> {
+        Ok(repo_kind(&self.open()?))
+    }
+
+    /// Returns path to the repository
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns name of the repository, i.e. name of its directory
+    pub fn name(&self) -> String {
+        let path = self.absolute_path();
+
+        match path.file_name() {
+            Some(name) => name.to_string_lossy().to_string(),
+            None => path.display().to_string(),
+        }
+    }
+
+    fn absolute_path(&self) -> PathBuf {
+        self.path
+            .canonicalize()
+            .unwrap_or_else(|_| self.path.clone())
+    }
+
+    /// Returns `output` or `CommandFailed` error if command exited with non-zero status
+    fn check(cmd: &str, status: ExitStatus, output: CommandOutput) -> Result<CommandOutput> {
+        match status.success() {
+            true => Ok(output),
+            false => Err(CommandFailed {
+                cmd: String::from(cmd),
+                output,
+            }
+            .into()),
+        }
+    }
+
+    /// Executes `command` and captures its output
+    ///
+    /// Returns `CommandFailed` error with captured output if command exits with non-zero status.
+    fn capture(cmd: &str, command: &mut Command) -> Result<CommandOutput> {
+        let output = command
+            .output()
+            .context(format!("Failed to execute: {}", cmd))?;
+
+        Repo::check(
+            cmd,
+            output.status,
+            CommandOutput {
+                exit_code: output.status.code(),
+                stdout: String::from_utf8_lossy(&output.stdout).to_string(),
+                stderr: String::from_utf8_lossy(&output.stderr).to_string(),
+            },
+        )
+    }
+
+    /// Executes `command` with output going directly to the terminal
+    ///
+    /// Returns `CommandFailed` error if command exits with non-zero status.
+    fn stream(cmd: &str, command: &mut Command) -> Result<CommandOutput> {
+        let status = command
+            .status()
+            .context(format!("Failed to execute: {}", cmd))?;
+
+        Repo::check(
+            cmd,
+            status,
+            CommandOutput {
+                exit_code: status.code(),
+                ..Default::default()
+            },
+        )
+    }
+
+    fn git_command(&self, args: &[String]) -> Command {
+        let mut command = Command::new("git");
+        command.current_dir(self.path.to_str().unwrap()).args(args);
+
+        command
+    }
+
+    /// Executes custom git command on a repository with output going directly to the terminal,
+    /// so returned output contains only exit code
+    ///
+    /// Returns `CommandFailed` error if command exits with non-zero status.
+    ///
+    /// # Arguments
+    ///
+    /// * `args` - arguments passed to git
+    pub fn custom_cmd_streamed(&self, args: &[String]) -> Result<CommandOutput> {
+        let cmd = format!("git {}", shell_words::join(args));
+
+        trace!(
+            "Executing command {} on repo located in {}",
+            cmd,
+            self.path.to_str().unwrap()
+        );
+
+        Repo::stream(&cmd, &mut self.git_command(args))
+    }
+
+    /// Fetches remotes selected in `settings` reporting progress of downloaded objects
+    ///
+    /// # Arguments
+    ///
+    /// * `settings` - remotes to fetch and fetch options
+    /// * `progress` - called whenever more objects are received from a remote
+    pub fn fetch_with_progress<P>(
+        &self,
+        settings: &FetchSettings,
+        mut progress: P,
+    ) -> Result<Vec<RemoteFetch>>
+    where
+        P: FnMut(&FetchProgress),
+    {
+        let repository = self.open()?;
+
+        let remotes: Vec<String> = match settings.remotes.is_empty() {
+            true => repository
+                .remotes()
+                .context("Failed to list remotes")?
+                .iter()
+                .flatten()
+                .map(String::from)
+                .collect(),
+            false => settings.remotes.clone(),
+        };
+
+        let config = repository.config().context("Failed to read git config")?;
+        let mut report = Vec::new();
+
+        for name in remotes {
+            trace!(
+                "Fetching remote {} of repo located in {}",
+                name,
+                self.path.display()
+            );
+
+            let mut remote = repository
+                .find_remote(&name)
+                .context(format!("Failed to find remote: {}", name))?;
+
+            let mut updated_refs = Vec::new();
+
+            {
+                let mut callbacks = RemoteCallbacks::new();
+                callbacks
+                    .credentials(credentials(&config))
+                    .transfer_progress(|stats| {
+                        progress(&FetchProgress {
+                            remote: &name,
+                            received_objects: stats.received_objects(),
+                            total_objects: stats.total_objects(),
+                            received_bytes: stats.received_bytes(),
+                        });
+                        true
+                    })
+                    .update_tips(|refname, old, new| {
+                        updated_refs.push(RefUpdate {
+                            name: String::from(refname),
+                            old: abbreviate(old),
+                            new: abbreviate(new),
+                        });
+                        true
+                    });
+
+                let mut options = FetchOptions::new();
+                options
+                    .remote_callbacks(callbacks)
+                    .prune(match settings.prune {
+                        true => FetchPrune::On,
+                        false => FetchPrune::Unspecified,
+                    })
+                    .download_tags(match settings.tags {
+                        FetchTags::Auto => AutotagOption::Auto,
+                        FetchTags::All => AutotagOption::All,
+                        FetchTags::None => AutotagOption::None,
+                    });
+
+                remote
+                    .fetch(&[] as &[&str], Some(&mut options), None)
+                    .context(format!("Failed to fetch remote: {}", name))?;
+            }
+
+            let stats = remote.stats();
+
+            report.push(RemoteFetch {
+                remote: name,
+                received_objects: stats.received_objects(),
+                total_objects: stats.total_objects(),
+                received_bytes: stats.received_bytes(),
+                updated_refs,
+            });
+        }
+
+        Ok(report)
+    }
+
+    /// Returns command executing `cmd` in user's shell with the repository as working directory
+    ///
+    /// Environment variables `RGIT_REPO_PATH`, `RGIT_REPO_NAME` and `RGIT_REPO_INDEX` describe the repository.
+    fn shell_command(&self, cmd: &str, index: usize) -> Command {
+        let shell = std::env::var("SHELL").unwrap_or_else(|_| String::from("/bin/sh"));
+
+        let mut command = Command::new(shell);
+        command
+            .current_dir(&self.path)
+            .arg("-c")
+            .arg(cmd)
+            .env("RGIT_REPO_PATH", self.absolute_path())
+            .env("RGIT_REPO_NAME", self.name())
+            .env("RGIT_REPO_INDEX", index.to_string());
+
+        command
+    }
+
+    /// Executes shell command on a repository and captures its output
+    ///
+    /// Returns `CommandFailed` error with captured output if command exits with non-zero status.
+    ///
+    /// # Arguments
+    ///
+    /// * `cmd` - command executed by user's shell
+    /// * `index` - index of the repository exposed to the command as `RGIT_REPO_INDEX`
+    pub fn shell_cmd(&self, cmd: &str, index: usize) -> Result<CommandOutput> {
+        trace!(
+            "Executing shell command {} on repo located in {}",
+            cmd,
+            self.path.to_str().unwrap()
+        );
+
+        Repo::capture(cmd, &mut self.shell_command(cmd, index))
+    }
+
+    /// Executes shell command on a repository with output going directly to the terminal,
+    /// so returned output contains only exit code
+    ///
+    /// Returns `CommandFailed` error if command exits with non-zero status.
+    ///
+    /// # Arguments
+    ///
+    /// * `cmd` - command executed by user's shell
+    /// * `index` - index of the repository exposed to the command as `RGIT_REPO_INDEX`
+    pub fn shell_cmd_streamed(&self, cmd: &str, index: usize) -> Result<CommandOutput> {
+        trace!(
+            "Executing shell command {} on repo located in {}",
+            cmd,
+            self.path.to_str().unwrap()
         );
+
+        Repo::stream(cmd, &mut self.shell_command(cmd, index))
     }
 }
 
 impl RepoOperations for Repo {
-    /// Executes custom git command on a repository
+    /// Executes custom git command on a repository and captures its output
+    ///
+    /// Returns `CommandFailed` error with captured output if command exits with non-zero status.
     ///
     /// # Arguments
     ///
-    /// * `cmd` - git command to execute
-    fn custom_cmd(&self, cmd: String) -> Result<()> {
+    /// * `args` - arguments passed to git
+    fn custom_cmd(&self, args: &[String]) -> Result<CommandOutput> {
+        let cmd = format!("git {}", shell_words::join(args));
+
         trace!(
             "Executing command {} on repo located in {}",
             cmd,
             self.path.to_str().unwrap()
         );
 
-        self.print_path();
+        Repo::capture(&cmd, &mut self.git_command(args))
+    }
+    /// Returns status of the repository together with current branch and its distance to upstream
+    ///
+    /// # Arguments
+    ///
+    /// * `ignored` - report also files ignored by git
+    fn status(&self, ignored: bool) -> Result<StatusReport> {
+        let repository = self.open_worktree()?;
+
+        let mut options = StatusOptions::new();
+        options
+            .include_untracked(true)
+            .include_ignored(ignored)
+            .renames_head_to_index(true);
 
-        let args: Vec<&str> = cmd.split(" ").collect();
+        let statuses = repository
+            .statuses(Some(&mut options))
+            .context("Failed to read status of the repository")?;
 
-        Command::new("git")
-            .current_dir(self.path.to_str().unwrap())
-            .args(args)
-            .status()
-            .context(format!("Failed to execute: git {}", cmd))?;
+        let mut report = StatusReport {
+            entries: statuses.iter().map(|entry| status_entry(&entry)).collect(),
+            ..Default::default()
+        };
 
-        Ok(())
+        report.branch = current_branch(&repository)?;
+
+        if let Some(branch) = &report.branch {
+            if let Some((upstream, ahead, behind)) = upstream_distance(&repository, branch)? {
+                report.upstream = Some(upstream);
+                report.ahead = ahead;
+                report.behind = behind;
+            }
+        }
+
+        Ok(report)
     }
-    /// Executes `git status --porcelain` on the repository
-    fn porcelain(&self) -> Result<()> {
-        let output = Command::new("git")
-            .current_dir(self.path.to_str().unwrap())
-            .arg("status")
-            .arg("--porcelain")
-            .output()
-            .context("Failed to execute: git status --porcelain")?;
+    /// Returns number of commits ahead and behind upstream for branches with configured upstream
+    ///
+    /// # Arguments
+    ///
+    /// * `all_branches` - check every local branch, not only the current one
+    fn sync_status(&self, all_branches: bool) -> Result<Vec<BranchSync>> {
+        let repository = self.open()?;
 
-        if output.stdout.len() == 0 {
-            trace!(
-                "Skipping status --porcelain on {}",
-                self.path.to_str().unwrap()
+        let branches = match all_branches {
+            true => local_branches(&repository)?,
+            false => current_branch(&repository)?.into_iter().collect(),
+        };
+
+        let mut report = Vec::new();
+
+        for branch in branches {
+            if let Some((upstream, ahead, behind)) = upstream_distance(&repository, &branch)? {
+                report.push(BranchSync {
+                    branch,
+                    upstream,
+                    ahead,
+                    behind,
+                });
+            }
+        }
+
+        Ok(report)
+    }
+    /// Returns state of every submodule, similar to `git submodule status`
+    fn submodule_status(&self) -> Result<Vec<SubmoduleState>> {
+        let repository = self.open_worktree()?;
+        let mut states = Vec::new();
+
+        for submodule in repository
+            .submodules()
+            .context("Failed to list submodules")?
+        {
+            let name = submodule.name().context("Invalid submodule name")?;
+            let status = repository
+                .submodule_status(name, SubmoduleIgnore::None)
+                .context(format!("Failed to read status of submodule {}", name))?;
+
+            let uninitialized = !status.contains(git2::SubmoduleStatus::IN_WD)
+                || status.contains(git2::SubmoduleStatus::WD_UNINITIALIZED);
+            let dirty = status.intersects(
+                git2::SubmoduleStatus::WD_INDEX_MODIFIED
+                    | git2::SubmoduleStatus::WD_WD_MODIFIED
+                    | git2::SubmoduleStatus::WD_UNTRACKED,
             );
-            return Ok(());
+
+            states.push(SubmoduleState {
+                path: submodule.path().display().to_string(),
+                recorded: submodule.index_id().and_then(abbreviate),
+                checked_out: match uninitialized {
+                    true => None,
+                    false => submodule.workdir_id().and_then(abbreviate),
+                },
+                uninitialized,
+                dirty,
+            });
+        }
+
+        Ok(states)
+    }
+    /// Fetches remotes selected in `settings`, similar to `git fetch`
+    ///
+    /// # Arguments
+    ///
+    /// * `settings` - remotes to fetch and fetch options
+    fn fetch(&self, settings: &FetchSettings) -> Result<Vec<RemoteFetch>> {
+        self.fetch_with_progress(settings, |_| {})
+    }
+    /// Updates current branch to its upstream without fetching it
+    ///
+    /// Update is refused if there are any changes in the working tree or, unless rebasing,
+    /// when the branch and its upstream have diverged.
+    ///
+    /// # Arguments
+    ///
+    /// * `strategy` - how the branch is updated
+    fn update(&self, strategy: UpdateStrategy) -> Result<UpdateReport> {
+        let repository = self.open_worktree()?;
+
+        let mut report = UpdateReport {
+            branch: current_branch(&repository)?,
+            upstream: None,
+            ahead: 0,
+            behind: 0,
+            outcome: UpdateOutcome::Detached,
+            old: None,
+            new: None,
+        };
+
+        let branch = match &report.branch {
+            Some(branch) => branch.clone(),
+            None => return Ok(report),
+        };
+
+        let (upstream, ahead, behind) = match upstream_distance(&repository, &branch)? {
+            Some(distance) => distance,
+            None => {
+                report.outcome = UpdateOutcome::NoUpstream;
+                return Ok(report);
+            }
+        };
+
+        report.upstream = Some(upstream);
+        report.ahead = ahead;
+        report.behind = behind;
+
+        report.outcome = match (ahead, behind) {
+            (0, 0) => UpdateOutcome::UpToDate,
+            (_, 0) => UpdateOutcome::Ahead,
+            _ if self.status(false)?.has_tracked_changes() => UpdateOutcome::Dirty,
+            (0, _) => UpdateOutcome::FastForwarded,
+            _ if strategy == UpdateStrategy::FastForward => UpdateOutcome::Diverged,
+            _ => UpdateOutcome::Rebased,
+        };
+
+        if report.outcome != UpdateOutcome::FastForwarded
+            && report.outcome != UpdateOutcome::Rebased
+        {
+            return Ok(report);
+        }
+
+        let local = repository
+            .find_branch(&branch, BranchType::Local)
+            .context(format!("Failed to find branch: {}", branch))?;
+        let old = local.get().target().context("Branch has no commits")?;
+        let target = local
+            .upstream()
+            .context("Failed to find upstream")?
+            .get()
+            .target()
+            .context("Upstream has no commits")?;
+
+        let updated = match report.outcome {
+            UpdateOutcome::FastForwarded => {
+                fast_forward(&repository, local.get(), target)?;
+                true
+            }
+            _ => rebase(&repository, local.get(), target)?,
+        };
+
+        if !updated {
+            report.outcome = UpdateOutcome::Conflicts;
+            return Ok(report);
         }
 
-        self.print_path();
+        let new = repository
+            .refname_to_id(&format!("refs/heads/{}", branch))
+            .context("Failed to read updated branch")?;
+
+        report.old = abbreviate(old);
+        report.new = abbreviate(new);
 
-        Ok(println!("{}", String::from_utf8_lossy(&output.stdout)))
+        Ok(report)
     }
-    /// Finds repositories which have cherry-picks in history
-    fn find_cherry_picks(&self) -> Result<Option<String>> {
-        let reflog = Command::new("git")
-            .current_dir(self.path.to_str().unwrap())
-            .arg("reflog")
-            .output()
-            .context("Failed to execute: git reflog")?;
-        let reflog = String::from_utf8_lossy(&reflog.stdout);
+    /// Finds commits present both on the checked out branch and on the `base`, similar to `git cherry`
+    ///
+    /// Commits are considered the same if one of them has `(cherry picked from commit ...)` trailer
+    /// pointing at the other one or if they introduce the same changes, i.e. have the same patch-id.
+    /// Fails if `base` isn't given and the branch has no upstream.
+    ///
+    /// # Arguments
+    ///
+    /// * `base` - revision the branch is compared with, upstream of the branch if not given
+    fn cherry_picks(&self, base: Option<&str>) -> Result<Vec<CherryPick>> {
+        let repository = self.open()?;
+
+        let head = match repository.head().ok().and_then(|head| head.target()) {
+            Some(head) => head,
+            None => return Ok(Vec::new()),
+        };
+
+        let base = match base {
+            Some(base) => repository
+                .revparse_single(base)
+                .and_then(|base| base.peel_to_commit())
+                .context(format!("Failed to find base: {}", base))?
+                .id(),
+            None => upstream_target(&repository)?
+                .context("No upstream branch to compare with, use --cherry-base")?,
+        };
+
+        let head_commits = commits_between(&repository, head, base)?;
+        let base_commits = commits_between(&repository, base, head)?;
+
+        let mut found = HashSet::new();
+        let mut cherry_picks = Vec::new();
+
+        let mut add = |commit: &Commit, equivalent: Oid, detected_by: Detection| {
+            if found.insert((commit.id(), equivalent)) {
+                cherry_picks.push(CherryPick {
+                    commit: abbreviate(commit.id()).unwrap_or_default(),
+                    summary: String::from(commit.summary().unwrap_or_default()),
+                    equivalent: abbreviate(equivalent).unwrap_or_default(),
+                    detected_by,
+                });
+            }
+        };
+
+        for commit in &head_commits {
+            for source in cherry_pick_sources(&repository, commit) {
+                if is_reachable(&repository, source, base)? {
+                    add(commit, source, Detection::Trailer);
+                }
+            }
+        }
+
+        for commit in &base_commits {
+            for source in cherry_pick_sources(&repository, commit) {
+                if is_reachable(&repository, source, head)? {
+                    add(
+                        &repository.find_commit(source)?,
+                        commit.id(),
+                        Detection::Trailer,
+                    );
+                }
+            }
+        }
+
+        let mut base_patches = HashMap::new();
+        for commit in &base_commits {
+            if let Some(patch_id) = patch_id(&repository, commit)? {
+                base_patches.entry(patch_id).or_insert_with(|| commit.id());
+            }
+        }
 
-        if reflog.contains("cherry-pick") {
-            self.print_path();
-            return Ok(Some(reflog.to_string()));
+        for commit in &head_commits {
+            if let Some(patch_id) = patch_id(&repository, commit)? {
+                if let Some(equivalent) = base_patches.get(&patch_id) {
+                    add(commit, *equivalent, Detection::PatchId);
+                }
+            }
         }
 
-        Ok(None)
+        Ok(cherry_picks)
     }
-    /// Prints all cherry picks found in history
-    fn print_cherry_picks(&self) -> Result<()> {
-        match self
-            .find_cherry_picks()
-            .context("Failed to find cherry picks")?
-        {
-            Some(reflog) => {
-                trace!("Printing cherry-picks line by line");
+    /// Finds commits of each branch missing on the other one
+    ///
+    /// Commits with the same changes or cherry-picked with `-x` from a commit on the other branch are
+    /// treated as present on both branches. Merge commits are not compared.
+    ///
+    /// # Arguments
+    ///
+    /// * `base` - revision of the first compared branch, e.g. `main`
+    /// * `head` - revision of the second compared branch, e.g. `release/1.0`
+    fn compare(&self, base: &str, head: &str) -> Result<Comparison> {
+        let repository = self.open()?;
+
+        let mut comparison = Comparison {
+            base: String::from(base),
+            head: String::from(head),
+            ..Default::default()
+        };
+
+        let resolve = |name: &str| {
+            repository
+                .revparse_single(name)
+                .and_then(|object| object.peel_to_commit())
+                .map(|commit| commit.id())
+                .ok()
+        };
 
-                let _: Vec<_> = reflog
-                    .lines()
-                    .filter(|&line| line.contains("cherry-pick"))
-                    .collect::<Vec<&str>>()
-                    .iter()
-                    .map(|cherry_pick| println!("{}", cherry_pick))
-                    .collect();
+        let (base_oid, head_oid) = match (resolve(base), resolve(head)) {
+            (Some(base), Some(head)) => (base, head),
+            (base_oid, head_oid) => {
+                if base_oid.is_none() {
+                    comparison.missing_branches.push(String::from(base));
+                }
+                if head_oid.is_none() {
+                    comparison.missing_branches.push(String::from(head));
+                }
+                return Ok(comparison);
             }
-            None => {}
+        };
+
+        let base_commits = commits_between(&repository, base_oid, head_oid)?;
+        let head_commits = commits_between(&repository, head_oid, base_oid)?;
+
+        comparison.missing_in_base = missing_commits(&repository, &head_commits, &base_commits)?;
+        comparison.missing_in_head = missing_commits(&repository, &base_commits, &head_commits)?;
+
+        Ok(comparison)
+    }
+    /// Lists commits of checked out branch matching the `filter`, newest first
+    ///
+    /// When `filter` range isn't limited by dates, at most `range.number` of matching commits is listed.
+    ///
+    /// # Arguments
+    ///
+    /// * `filter` - describes listed commits
+    fn history(&self, filter: &HistoryFilter) -> Result<Vec<CommitSummary>> {
+        let repository = self.open()?;
+
+        let mut revwalk = repository.revwalk().context("Failed to walk history")?;
+        if repository.head().is_err() {
+            return Ok(Vec::new());
         }
+        revwalk.push_head().context("Failed to walk history")?;
+        revwalk
+            .set_sorting(Sort::TOPOLOGICAL | Sort::TIME)
+            .context("Failed to walk history")?;
 
-        Ok(())
+        let mailmap = repository.mailmap().context("Failed to read mailmap")?;
+        let range = &filter.range;
+        let mut commits = Vec::new();
+
+        for oid in revwalk {
+            let commit = repository
+                .find_commit(oid.context("Failed to walk history")?)
+                .context("Failed to find commit")?;
+            let time = commit.time().seconds();
+
+            if range.after.is_some_and(|after| time < after) {
+                break;
+            }
+            if range.before.is_some_and(|before| time > before)
+                || !matches_filter(&repository, &mailmap, &commit, filter)?
+            {
+                continue;
+            }
+
+            commits.push(commit_summary(&commit));
+
+            if !range.is_date_range() && commits.len() >= range.number as usize {
+                break;
+            }
+        }
+
+        Ok(commits)
     }
-    /// Print repository if there is an author in last `number` of commits
+    /// Finds lines of tracked files matching the pattern, similar to `git grep`
+    ///
+    /// Binary files and submodules are skipped.
     ///
     /// # Arguments
     ///
-    /// * `number` - last number of commits to look into
-    /// * `author` - author to look for
-    fn print_commits_with_author(&self, number: u32, author: &str) -> Result<()> {
-        let number = format!("-{}", number);
-        let args = vec![
-            "log",
-            "--graph",
-            "--pretty=%h -%d %s (%cr) <%an>",
-            "--abbrev-commit",
-            &number,
-        ];
+    /// * `settings` - searched pattern, revision and files
+    fn grep(&self, settings: &GrepSettings) -> Result<Vec<GrepMatch>> {
+        let repository = self.open()?;
+        let pathspec = Pathspec::new(settings.paths.iter()).context("Invalid pathspec")?;
+        let matches_pathspec =
+            |path: &str| pathspec.matches_path(Path::new(path), PathspecFlags::DEFAULT);
 
-        let log = Command::new("git")
-            .current_dir(self.path.to_str().unwrap())
-            .args(args)
-            .output()
-            .context("Failed to execute: git log")?;
+        let mut matches = Vec::new();
 
-        let log = String::from_utf8(log.stdout)?;
+        if let Some(revision) = &settings.revision {
+            let tree = repository
+                .revparse_single(revision)
+                .and_then(|object| object.peel_to_tree())
+                .context(format!("Failed to find revision {}", revision))?;
 
-        let commits = log
-            .lines()
-            .filter(|&line| line.contains(author))
-            .collect::<Vec<&str>>();
+            let mut files = Vec::new();
+            tree.walk(TreeWalkMode::PreOrder, |root, entry| {
+                if entry.kind() == Some(ObjectType::Blob) {
+                    let path = format!("{}{}", root, entry.name().unwrap_or_default());
+                    files.push((path, entry.id()));
+                }
+                TreeWalkResult::Ok
+            })
+            .context("Failed to walk files of the revision")?;
 
-        if commits.len() == 0 {
-            trace!(
-                "Skipping printing commits with author for {}",
-                self.path.to_str().unwrap()
-            );
-            return Ok(());
+            for (path, oid) in files {
+                if !matches_pathspec(&path) {
+                    continue;
+                }
+
+                let blob = repository.find_blob(oid).context("Failed to find file")?;
+                matches.extend(grep_content(&path, blob.content(), &settings.pattern));
+            }
+
+            return Ok(matches);
         }
 
-        self.print_path();
+        let workdir = repository.workdir().context(format!(
+            "{} is a bare repository without a working tree",
+            self.path.display()
+        ))?;
+        let index = repository.index().context("Failed to read index")?;
+        let mut previous = None;
 
-        let _: Vec<_> = commits
-            .iter()
-            .map(|commit| println!("{}", commit))
-            .collect();
+        for entry in index.iter() {
+            let path = String::from_utf8_lossy(&entry.path).to_string();
 
-        Ok(())
+            // conflicted files have several entries in the index, they are searched once
+            if entry.mode == FILE_MODE_COMMIT
+                || entry.mode == FILE_MODE_LINK
+                || previous.as_ref() == Some(&path)
+                || !matches_pathspec(&path)
+            {
+                continue;
+            }
+
+            // files removed from the working tree have nothing to search
+            if let Ok(content) = std::fs::read(workdir.join(&path)) {
+                matches.extend(grep_content(&path, &content, &settings.pattern));
+            }
+
+            previous = Some(path);
+        }
+
+        Ok(matches)
     }
-}
+    /// Finds commits authored or committed by any of `authors` in given `range` of commits
+    ///
+    /// Identities are resolved with `.mailmap` of the repository before matching.
+    ///
+    /// # Arguments
+    ///
+    /// * `range` - number of the newest matching commits or dates of commits to look into
+    /// * `authors` - authors to look for
+    fn commits_with_author(
+        &self,
+        range: &LogRange,
+        authors: &AuthorMatcher,
+    ) -> Result<Vec<CommitMatch>> {
+        let repository = self.open()?;
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use anyhow::bail;
-    use tempfile::TempDir;
+        let mut revwalk = repository.revwalk().context("Failed to walk history")?;
+        if repository.head().is_err() {
+            return Ok(Vec::new());
+        }
+        revwalk.push_head().context("Failed to walk history")?;
+        revwalk
+            .set_sorting(Sort::TOPOLOGICAL | Sort::TIME)
+            .context("Failed to walk history")?;
 
-    #[test]
-    fn repo_provide_path_without_git_repository() -> Result<()> {
-        let path = TempDir::new()?;
+        let mailmap = repository.mailmap().context("Failed to read mailmap")?;
+        let decorations = decorations(&repository)?;
+        let mut commits = Vec::new();
 
-        match Repo::new(path.path().to_str().unwrap()) {
-            Some(_) => bail!("Oops, repository doesn't exist, repo should not be created"),
-            None => Ok(()),
+        for oid in revwalk {
+            let commit = repository
+                .find_commit(oid.context("Failed to walk history")?)
+                .context("Failed to find commit")?;
+            let time = commit.time().seconds();
+
+            if range.after.is_some_and(|after| time < after) {
+                break;
+            }
+            if range.before.is_some_and(|before| time > before)
+                || !matches_author(&commit, &mailmap, authors)?
+            {
+                continue;
+            }
+
+            let author = commit
+                .author_with_mailmap(&mailmap)
+                .context("Failed to resolve author")?;
+
+            commits.push(CommitMatch {
+                commit: abbreviate(commit.id()).unwrap_or_default(),
+                refs: decorations
+                    .get(&commit.id())
+                    .map(|refs| refs.join(", "))
+                    .unwrap_or_default(),
+                summary: String::from(commit.summary().unwrap_or_default()),
+                author: String::from(author.name().unwrap_or_default()),
+                email: String::from(author.email().unwrap_or_default()),
+                time,
+            });
+
+            if !range.is_date_range() && commits.len() >= range.number as usize {
+                break;
+            }
+        }
+
+        Ok(commits)
+    }
+    /// Checks if the current state of the repository satisfies all predicates of `filter`
+    ///
+    /// Bare repositories are never dirty.
+    ///
+    /// # Arguments
+    ///
+    /// * `filter` - checked predicates
+    fn matches_state(&self, filter: &StateFilter) -> Result<bool> {
+        let repository = self.open()?;
+
+        if filter.dirty && !is_dirty(&repository)? {
+            return Ok(false);
+        }
+
+        let branch = current_branch(&repository)?;
+
+        if let Some(glob) = &filter.branch {
+            if !branch.as_ref().is_some_and(|branch| glob.is_match(branch)) {
+                return Ok(false);
+            }
+        }
+
+        if let Some(remote) = &filter.remote {
+            if !remote_urls(&repository)?
+                .iter()
+                .any(|url| remote.is_match(url))
+            {
+                return Ok(false);
+            }
+        }
+
+        if filter.behind_upstream {
+            let behind = match &branch {
+                Some(branch) => {
+                    upstream_distance(&repository, branch)?.is_some_and(|(_, _, behind)| behind > 0)
+                }
+                None => false,
+            };
+
+            if !behind {
+                return Ok(false);
+            }
         }
+
+        if let Some(path) = &filter.path {
+            if !contains_path(&repository, path)? {
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+}
+
+/// Checks if the working tree has staged, unstaged, conflicted or untracked files
+fn is_dirty(repository: &Repository) -> Result<bool> {
+    if repository.is_bare() {
+        return Ok(false);
+    }
+
+    let mut options = StatusOptions::new();
+    options.include_untracked(true).include_ignored(false);
+
+    let statuses = repository
+        .statuses(Some(&mut options))
+        .context("Failed to read status of the repository")?;
+
+    Ok(!statuses.is_empty())
+}
+
+/// Returns URLs of all remotes of the repository
+fn remote_urls(repository: &Repository) -> Result<Vec<String>> {
+    let remotes = repository.remotes().context("Failed to list remotes")?;
+
+    Ok(remotes
+        .iter()
+        .flatten()
+        .filter_map(|name| repository.find_remote(name).ok())
+        .filter_map(|remote| remote.url().map(String::from))
+        .collect())
+}
+
+/// Checks if `path` exists in the working tree, or in HEAD of bare repositories
+fn contains_path(repository: &Repository, path: &Path) -> Result<bool> {
+    if let Some(workdir) = repository.workdir() {
+        return Ok(workdir.join(path).symlink_metadata().is_ok());
+    }
+
+    let head = match repository.head() {
+        Ok(head) => head,
+        Err(e) if e.code() == ErrorCode::UnbornBranch => return Ok(false),
+        Err(e) => return Err(e).context("Failed to read HEAD"),
+    };
+
+    let tree = head.peel_to_tree().context("Failed to read tree of HEAD")?;
+
+    Ok(tree.get_path(path).is_ok())
+}
+
+/// Returns kind of the repository opened with libgit2
+pub fn repo_kind(repository: &Repository) -> RepoKind {
+    match (repository.is_bare(), repository.is_worktree()) {
+        (true, _) => RepoKind::Bare,
+        (false, true) => RepoKind::Worktree,
+        (false, false) => RepoKind::Normal,
+    }
+}
+
+/// Returns lines of the file matching the `pattern`, nothing for binary files
+fn grep_content(path: &str, content: &[u8], pattern: &Regex) -> Vec<GrepMatch> {
+    // the same heuristic as git uses to detect binary files
+    if content.iter().take(8000).any(|byte| *byte == 0) {
+        return Vec::new();
+    }
+
+    String::from_utf8_lossy(content)
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| pattern.is_match(line))
+        .map(|(index, line)| GrepMatch {
+            path: String::from(path),
+            line: index + 1,
+            text: String::from(line),
+        })
+        .collect()
+}
+
+/// Converts libgit2 status of a single file into status entry similar to `git status --porcelain`
+fn status_entry(entry: &git2::StatusEntry) -> StatusEntry {
+    let status = entry.status();
+    let path = String::from_utf8_lossy(entry.path_bytes()).to_string();
+
+    let staged = match status {
+        s if s.is_index_new() => Some(Change::Added),
+        s if s.is_index_modified() => Some(Change::Modified),
+        s if s.is_index_deleted() => Some(Change::Deleted),
+        s if s.is_index_renamed() => Some(Change::Renamed),
+        s if s.is_index_typechange() => Some(Change::TypeChanged),
+        _ => None,
+    };
+
+    let unstaged = match status {
+        s if s.is_wt_modified() => Some(Change::Modified),
+        s if s.is_wt_deleted() => Some(Change::Deleted),
+        s if s.is_wt_renamed() => Some(Change::Renamed),
+        s if s.is_wt_typechange() => Some(Change::TypeChanged),
+        _ => None,
+    };
+
+    let state = match status {
+        s if s.is_conflicted() => FileState::Conflicted,
+        s if s.is_ignored() => FileState::Ignored,
+        s if s.is_wt_new() => FileState::Untracked,
+        _ => FileState::Tracked,
+    };
+
+    let code = |change: Option<Change>| change.map_or(' ', |change| change.code());
+
+    let status = match state {
+        FileState::Conflicted => String::from("UU"),
+        FileState::Ignored => String::from("!!"),
+        FileState::Untracked => String::from("??"),
+        FileState::Tracked => format!("{}{}", code(staged), code(unstaged)),
+    };
+
+    let renamed = |delta: Option<DiffDelta>| {
+        delta.and_then(|delta| {
+            Some(format!(
+                "{} -> {}",
+                delta.old_file().path()?.display(),
+                delta.new_file().path()?.display()
+            ))
+        })
+    };
+
+    let path = match (staged, unstaged) {
+        (Some(Change::Renamed), _) => renamed(entry.head_to_index()),
+        (_, Some(Change::Renamed)) => renamed(entry.index_to_workdir()),
+        _ => None,
+    }
+    .unwrap_or(path);
+
+    StatusEntry {
+        status,
+        path,
+        state,
+        staged,
+        unstaged,
+    }
+}
+
+/// Returns name of the branch HEAD points at, `None` if HEAD is detached
+///
+/// Works also for branches without any commits yet.
+pub fn current_branch(repository: &Repository) -> Result<Option<String>> {
+    let head = repository
+        .find_reference("HEAD")
+        .context("Failed to find HEAD")?;
+
+    Ok(head
+        .symbolic_target()
+        .and_then(|target| target.strip_prefix("refs/heads/"))
+        .map(String::from))
+}
+
+/// Returns abbreviated hash of the object, `None` for zero hash
+fn abbreviate(oid: Oid) -> Option<String> {
+    match oid.is_zero() {
+        true => None,
+        false => Some(oid.to_string()[..7].to_string()),
+    }
+}
+
+/// Returns commit the upstream of checked out branch points at, `None` if there is no upstream
+fn upstream_target(repository: &Repository) -> Result<Option<Oid>> {
+    let branch = match current_branch(repository)? {
+        Some(branch) => branch,
+        None => return Ok(None),
+    };
+
+    let upstream = repository
+        .find_branch(&branch, BranchType::Local)
+        .and_then(|branch| branch.upstream());
+
+    Ok(upstream.ok().and_then(|upstream| upstream.get().target()))
+}
+
+/// Returns commits reachable from `from` but not from `hidden`, without merge commits
+fn commits_between(repository: &Repository, from: Oid, hidden: Oid) -> Result<Vec<Commit<'_>>> {
+    let mut revwalk = repository.revwalk().context("Failed to walk history")?;
+    revwalk.push(from).context("Failed to walk history")?;
+    revwalk.hide(hidden).context("Failed to walk history")?;
+
+    let mut commits = Vec::new();
+
+    for oid in revwalk {
+        let commit = repository
+            .find_commit(oid.context("Failed to walk history")?)
+            .context("Failed to find commit")?;
+
+        if commit.parent_count() <= 1 {
+            commits.push(commit);
+        }
+    }
+
+    Ok(commits)
+}
+
+/// Returns true if `commit` is `tip` or one of its ancestors
+fn is_reachable(repository: &Repository, commit: Oid, tip: Oid) -> Result<bool> {
+    Ok(commit == tip
+        || repository
+            .graph_descendant_of(tip, commit)
+            .context("Failed to check commit ancestry")?)
+}
+
+/// Returns existing commits `commit` was cherry-picked from according to
+/// `(cherry picked from commit ...)` trailers in its message
+fn cherry_pick_sources(repository: &Repository, commit: &Commit) -> Vec<Oid> {
+    commit
+        .message()
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| {
+            line.trim()
+                .strip_prefix("(cherry picked from commit ")?
+                .strip_suffix(')')
+        })
+        .filter_map(|source| {
+            repository
+                .revparse_single(source)
+                .and_then(|source| source.peel_to_commit())
+                .ok()
+        })
+        .map(|source| source.id())
+        .collect()
+}
+
+/// Returns `commits` which are not present in `other` commits
+///
+/// Commits with the same patch-id as any of `other` commits, cherry-picked with `-x` to any of them
+/// or from any of them are treated as present.
+fn missing_commits(
+    repository: &Repository,
+    commits: &[Commit],
+    other: &[Commit],
+) -> Result<Vec<CommitSummary>> {
+    let mut patch_ids = HashSet::new();
+    let mut picked = HashSet::new();
+    let other_ids: HashSet<Oid> = other.iter().map(|commit| commit.id()).collect();
+
+    for commit in other {
+        if let Some(patch_id) = patch_id(repository, commit)? {
+            patch_ids.insert(patch_id);
+        }
+        picked.extend(cherry_pick_sources(repository, commit));
+    }
+
+    let mut missing = Vec::new();
+
+    for commit in commits {
+        if picked.contains(&commit.id())
+            || cherry_pick_sources(repository, commit)
+                .iter()
+                .any(|source| other_ids.contains(source))
+        {
+            continue;
+        }
+
+        if let Some(patch_id) = patch_id(repository, commit)? {
+            if patch_ids.contains(&patch_id) {
+                continue;
+            }
+        }
+
+        missing.push(commit_summary(commit));
+    }
+
+    Ok(missing)
+}
+
+/// Returns basic information about the commit
+fn commit_summary(commit: &Commit) -> CommitSummary {
+    let author = commit.author();
+
+    CommitSummary {
+        commit: abbreviate(commit.id()).unwrap_or_default(),
+        summary: String::from(commit.summary().unwrap_or_default()),
+        author: String::from(author.name().unwrap_or_default()),
+        email: String::from(author.email().unwrap_or_default()),
+        time: commit.time().seconds(),
+    }
+}
+
+/// Returns true if author or committer of the commit, resolved with the `mailmap`, matches `authors`
+fn matches_author(commit: &Commit, mailmap: &Mailmap, authors: &AuthorMatcher) -> Result<bool> {
+    let signatures = [
+        commit
+            .author_with_mailmap(mailmap)
+            .context("Failed to resolve author")?,
+        commit
+            .committer_with_mailmap(mailmap)
+            .context("Failed to resolve committer")?,
+    ];
+
+    Ok(signatures.iter().any(|signature| {
+        authors.is_match(
+            signature.name().unwrap_or_default(),
+            signature.email().unwrap_or_default(),
+        )
+    }))
+}
+
+/// Returns names of references pointing at commits, decorated like in `git log --decorate`,
+/// e.g. `HEAD -> master`, `origin/master`, `tag: v1.0`
+fn decorations(repository: &Repository) -> Result<HashMap<Oid, Vec<String>>> {
+    let mut decorations: HashMap<Oid, Vec<String>> = HashMap::new();
+
+    let head = repository.head().ok();
+    let head_name = head
+        .as_ref()
+        .filter(|head| head.is_branch())
+        .and_then(|head| head.name().map(String::from));
+
+    if let Some(head) = &head {
+        if let Some(oid) = head.target() {
+            let decoration = match head.is_branch() {
+                true => format!("HEAD -> {}", head.shorthand().unwrap_or_default()),
+                false => String::from("HEAD"),
+            };
+            decorations.entry(oid).or_default().push(decoration);
+        }
+    }
+
+    for reference in repository
+        .references()
+        .context("Failed to list references")?
+    {
+        let reference = reference.context("Failed to read reference")?;
+
+        if reference.symbolic_target().is_some() || reference.name() == head_name.as_deref() {
+            continue;
+        }
+
+        let oid = match reference.peel_to_commit() {
+            Ok(commit) => commit.id(),
+            Err(_) => continue,
+        };
+        let name = reference.shorthand().unwrap_or_default();
+        let decoration = match reference.is_tag() {
+            true => format!("tag: {}", name),
+            false => String::from(name),
+        };
+
+        decorations.entry(oid).or_default().push(decoration);
+    }
+
+    Ok(decorations)
+}
+
+/// Returns true if the commit matches author, message and paths of the `filter`
+///
+/// Merge commits never match paths, like in `git log` they would repeat changes of merged commits.
+fn matches_filter(
+    repository: &Repository,
+    mailmap: &Mailmap,
+    commit: &Commit,
+    filter: &HistoryFilter,
+) -> Result<bool> {
+    if let Some(authors) = &filter.author {
+        if !matches_author(commit, mailmap, authors)? {
+            return Ok(false);
+        }
+    }
+
+    if let Some(message) = &filter.message {
+        if !message.is_match(commit.message().unwrap_or_default()) {
+            return Ok(false);
+        }
+    }
+
+    if filter.paths.is_empty() {
+        return Ok(true);
+    }
+    if commit.parent_count() > 1 {
+        return Ok(false);
+    }
+
+    let parent = match commit.parent_count() {
+        0 => None,
+        _ => Some(commit.parent(0)?.tree()?),
+    };
+
+    let mut options = DiffOptions::new();
+    for path in &filter.paths {
+        options.pathspec(path);
+    }
+
+    let diff = repository
+        .diff_tree_to_tree(parent.as_ref(), Some(&commit.tree()?), Some(&mut options))
+        .context("Failed to diff commit")?;
+
+    Ok(diff.deltas().len() > 0)
+}
+
+/// Returns patch-id of changes introduced by the commit, `None` for commits without changes
+fn patch_id(repository: &Repository, commit: &Commit) -> Result<Option<Oid>> {
+    let parent = match commit.parent_count() {
+        0 => None,
+        _ => Some(commit.parent(0)?.tree()?),
+    };
+
+    let diff = repository
+        .diff_tree_to_tree(parent.as_ref(), Some(&commit.tree()?), None)
+        .context("Failed to diff commit")?;
+
+    if diff.deltas().len() == 0 {
+        return Ok(None);
+    }
+
+    Ok(Some(
+        diff.patchid(None).context("Failed to compute patch-id")?,
+    ))
+}
+
+/// Fast-forwards checked out `branch` to `target` commit
+///
+/// Working tree is updated in a safe way, so no local changes are ever overwritten.
+fn fast_forward(repository: &Repository, branch: &Reference, target: Oid) -> Result<()> {
+    let commit = repository
+        .find_commit(target)
+        .context("Failed to find upstream commit")?;
+
+    repository
+        .checkout_tree(commit.as_object(), Some(CheckoutBuilder::new().safe()))
+        .context("Failed to check out upstream commit")?;
+
+    let name = branch.name().context("Invalid branch name")?;
+
+    repository
+        .reference(
+            name,
+            target,
+            true,
+            &format!("rgit update: fast-forward to {}", target),
+        )
+        .context("Failed to update branch")?;
+
+    Ok(())
+}
+
+/// Rebases commits of checked out `branch` onto `target` commit
+///
+/// Commits already present in `target` are skipped. Rebase is aborted when any commit
+/// doesn't apply cleanly, leaving the branch untouched.
+/// Returns true if branch was rebased, false if rebase was aborted because of conflicts.
+fn rebase(repository: &Repository, branch: &Reference, target: Oid) -> Result<bool> {
+    let local = repository
+        .reference_to_annotated_commit(branch)
+        .context("Failed to read branch")?;
+    let onto = repository
+        .find_annotated_commit(target)
+        .context("Failed to find upstream commit")?;
+    let signature = repository
+        .signature()
+        .context("Failed to get signature of git user")?;
+
+    let mut rebase = repository
+        .rebase(Some(&local), Some(&onto), None, None)
+        .context("Failed to start rebase")?;
+
+    while let Some(operation) = rebase.next() {
+        let conflicts = match operation {
+            Ok(_) => repository.index()?.has_conflicts(),
+            Err(err) if err.code() == ErrorCode::MergeConflict => true,
+            Err(err) => {
+                rebase.abort().context("Failed to abort rebase")?;
+                return Err(err).context("Failed to apply commit");
+            }
+        };
+
+        if conflicts {
+            rebase.abort().context("Failed to abort rebase")?;
+            return Ok(false);
+        }
+
+        match rebase.commit(None, &signature, None) {
+            Err(err) if err.code() != ErrorCode::Applied => {
+                rebase.abort().context("Failed to abort rebase")?;
+                return Err(err).context("Failed to commit rebased changes");
+            }
+            _ => trace!("Applied commit"),
+        }
+    }
+
+    rebase
+        .finish(Some(&signature))
+        .context("Failed to finish rebase")?;
+
+    Ok(true)
+}
+
+/// Returns callback providing credentials for remotes the same way git does it,
+/// i.e. with ssh agent for ssh remotes and credential helpers for https remotes
+///
+/// Every kind of credentials is tried only once, so fetch fails instead of looping on wrong credentials.
+fn credentials(
+    config: &git2::Config,
+) -> impl FnMut(&str, Option<&str>, CredentialType) -> std::result::Result<Cred, git2::Error> + '_ {
+    let mut tried = CredentialType::empty();
+
+    move |url, username, allowed| {
+        let username = username.unwrap_or("git");
+
+        if allowed.contains(CredentialType::USERNAME) && !tried.contains(CredentialType::USERNAME) {
+            tried |= CredentialType::USERNAME;
+            return Cred::username(username);
+        }
+        if allowed.contains(CredentialType::SSH_KEY) && !tried.contains(CredentialType::SSH_KEY) {
+            tried |= CredentialType::SSH_KEY;
+            return Cred::ssh_key_from_agent(username);
+        }
+        if allowed.contains(CredentialType::USER_PASS_PLAINTEXT)
+            && !tried.contains(CredentialType::USER_PASS_PLAINTEXT)
+        {
+            tried |= CredentialType::USER_PASS_PLAINTEXT;
+            return Cred::credential_helper(config, url, Some(username));
+        }
+        if allowed.contains(CredentialType::DEFAULT) && !tried.contains(CredentialType::DEFAULT) {
+            tried |= CredentialType::DEFAULT;
+            return Cred::default();
+        }
+
+        Err(git2::Error::from_str("No more credentials to try"))
+    }
+}
+
+/// Returns names of all local branches
+fn local_branches(repository: &Repository) -> Result<Vec<String>> {
+    let mut names = Vec::new();
+
+    for branch in repository
+        .branches(Some(BranchType::Local))
+        .context("Failed to list local branches")?
+    {
+        let (branch, _) = branch.context("Failed to read local branch")?;
+
+        if let Some(name) = branch.name().context("Failed to read branch name")? {
+            names.push(String::from(name));
+        }
+    }
+
+    Ok(names)
+}
+
+/// Returns name of the upstream of local `branch` and number of commits the branch is ahead
+/// and behind it, `None` if the branch has no upstream or no commits
+fn upstream_distance(
+    repository: &Repository,
+    branch: &str,
+) -> Result<Option<(String, usize, usize)>> {
+    let branch = match repository.find_branch(branch, BranchType::Local) {
+        Ok(branch) => branch,
+        Err(_) => return Ok(None),
+    };
+
+    let upstream = match branch.upstream() {
+        Ok(upstream) => upstream,
+        Err(_) => return Ok(None),
+    };
+
+    let (local, remote) = match (branch.get().target(), upstream.get().target()) {
+        (Some(local), Some(remote)) => (local, remote),
+        _ => return Ok(None),
+    };
+
+    let (ahead, behind) = repository
+        .graph_ahead_behind(local, remote)
+        .context("Failed to compare branch with upstream")?;
+
+    let name = upstream
+        .name()
+        .context("Failed to read upstream name")?
+        .unwrap_or_default();
+
+    Ok(Some((String::from(name), ahead, behind)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exec::test_utils::init_repo;
+    use anyhow::bail;
+    use tempfile::TempDir;
+
+    #[test]
+    fn repo_provide_path_without_git_repository() -> Result<()> {
+        let path = TempDir::new()?;
+
+        match Repo::new(path.path().to_str().unwrap()) {
+            Some(_) => bail!("Oops, repository doesn't exist, repo should not be created"),
+            None => Ok(()),
+        }
+    }
+
+    #[test]
+    fn custom_cmd_passes_arguments_untouched() -> Result<()> {
+        let (_path, repository, repo) = init_repo()?;
+
+        let args = shell_words::split("commit --allow-empty -m 'fix  build'")?;
+
+        repo.custom_cmd(&args)?;
+
+        let head = repository.head()?.peel_to_commit()?;
+        assert_eq!(head.message(), Some("fix  build\n"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn shell_cmd_sets_environment() -> Result<()> {
+        let root = TempDir::new()?;
+        let path = root.path().join("layer");
+        let _ = Repository::init(&path)?;
+
+        let repo = Repo::new(path.to_str().unwrap()).unwrap();
+
+        let output = repo.shell_cmd(
+            "echo \"$RGIT_REPO_NAME:$RGIT_REPO_INDEX:$RGIT_REPO_PATH:$(pwd)\"",
+            7,
+        )?;
+
+        let path = path.canonicalize()?;
+        assert_eq!(
+            output.stdout,
+            format!("layer:7:{}:{}\n", path.display(), path.display())
+        );
+
+        match repo.shell_cmd("exit 3", 0) {
+            Ok(_) => bail!("Oops, command failed, error should be returned"),
+            Err(err) => assert_eq!(
+                err.downcast_ref::<CommandFailed>()
+                    .unwrap()
+                    .output
+                    .exit_code,
+                Some(3)
+            ),
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn status_and_commits_with_author() -> Result<()> {
+        let (path, repository, repo) = init_repo()?;
+
+        let signature = git2::Signature::now("Jane Doe", "jane@example.com")?;
+        let tree = repository.find_tree(repository.index()?.write_tree()?)?;
+        repository.commit(
+            Some("HEAD"),
+            &signature,
+            &signature,
+            "Add layer",
+            &tree,
+            &[],
+        )?;
+
+        std::fs::write(path.path().join("untracked"), "")?;
+
+        assert_eq!(
+            repo.status(false)?.entries,
+            vec![StatusEntry {
+                status: String::from("??"),
+                path: String::from("untracked"),
+                state: FileState::Untracked,
+                staged: None,
+                unstaged: None,
+            }]
+        );
+
+        let authors = AuthorMatcher::new(&[String::from("Jane")])?;
+        let commits = repo.commits_with_author(&LogRange::last(10), &authors)?;
+        assert_eq!(commits.len(), 1);
+        assert_eq!(commits[0].summary, "Add layer");
+        assert_eq!(commits[0].email, "jane@example.com");
+
+        let authors = AuthorMatcher::new(&[String::from("John")])?;
+        assert!(repo
+            .commits_with_author(&LogRange::last(10), &authors)?
+            .is_empty());
+        assert!(repo.cherry_picks(None).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn commits_with_author_resolves_mailmap_and_committer() -> Result<()> {
+        let (path, repository, repo) = init_repo()?;
+
+        let tree = repository.find_tree(repository.index()?.write_tree()?)?;
+        let old = git2::Signature::now("J. Doe", "jd@old.example.com")?;
+        let bot = git2::Signature::now("Build Bot", "bot@example.com")?;
+        let first = repository.commit(Some("HEAD"), &old, &old, "Add layer", &tree, &[])?;
+        let first = repository.find_commit(first)?;
+        let john = git2::Signature::now("John Doe", "john@example.com")?;
+        let second =
+            repository.commit(Some("HEAD"), &john, &bot, "Update layer", &tree, &[&first])?;
+        repository.tag_lightweight("v1.0", &repository.find_object(second, None)?, false)?;
+
+        std::fs::write(
+            path.path().join(".mailmap"),
+            "Jane Doe <jane@example.com> <jd@old.example.com>\n",
+        )?;
+
+        let summaries = |authors: &[&str], range: LogRange| -> Result<Vec<CommitMatch>> {
+            let authors: Vec<String> = authors.iter().map(|author| author.to_string()).collect();
+            repo.commits_with_author(&range, &AuthorMatcher::new(&authors)?)
+        };
+
+        let commits = summaries(&["^jane@example.com$"], LogRange::last(10))?;
+        assert_eq!(commits.len(), 1);
+        assert_eq!(commits[0].summary, "Add layer");
+        assert_eq!(commits[0].author, "Jane Doe");
+        assert!(summaries(&["J. Doe"], LogRange::last(10))?.is_empty());
+
+        let commits = summaries(&["Build Bot"], LogRange::last(10))?;
+        assert_eq!(commits.len(), 1);
+        assert_eq!(commits[0].author, "John Doe");
+        assert_eq!(commits[0].refs, "HEAD -> master, tag: v1.0");
+
+        assert_eq!(summaries(&["^Jane", "^John"], LogRange::last(10))?.len(), 2);
+        assert_eq!(summaries(&["^Jane", "^John"], LogRange::last(1))?.len(), 1);
+        assert_eq!(summaries(&["^Jane"], LogRange::last(1))?.len(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn submodule_status_reports_uninitialized_moved_and_dirty_submodules() -> Result<()> {
+        let upstream_dir = TempDir::new()?;
+        let upstream = Repository::init(upstream_dir.path())?;
+        commit_file(&upstream, "README", "layer")?;
+
+        let (path, repository, repo) = init_repo()?;
+        let url = upstream_dir.path().to_str().unwrap();
+
+        for name in ["sub", "other"] {
+            let mut submodule = repository.submodule(url, Path::new(name), true)?;
+            submodule.clone(None)?;
+            submodule.add_finalize()?;
+        }
+        commit_file(&repository, "README", "product")?;
+
+        std::fs::remove_dir_all(path.path().join("other"))?;
+
+        let states = repo.submodule_status()?;
+        let recorded = abbreviate(upstream.head()?.target().unwrap());
+
+        assert_eq!(states.len(), 2);
+        assert_eq!(states[0].path, "other");
+        assert!(states[0].uninitialized);
+        assert_eq!(states[0].checked_out, None);
+        assert_eq!(states[1].path, "sub");
+        assert!(states[1].is_clean());
+        assert_eq!(states[1].recorded, recorded);
+
+        let submodule = Repository::open(path.path().join("sub"))?;
+        let moved = commit_file(&submodule, "conf", "moved")?;
+        std::fs::write(path.path().join("sub/untracked"), "")?;
+
+        let states = repo.submodule_status()?;
+        assert_eq!(states[1].recorded, recorded);
+        assert_eq!(states[1].checked_out, abbreviate(moved));
+        assert!(states[1].dirty);
+
+        Ok(())
+    }
+
+    #[test]
+    fn bare_repositories_have_no_working_tree() -> Result<()> {
+        let path = TempDir::new()?;
+        let _ = Repository::init_bare(path.path())?;
+
+        let repo = Repo::new(path.path().to_str().unwrap()).unwrap();
+
+        assert_eq!(repo.kind()?, RepoKind::Bare);
+        assert!(format!("{:#}", repo.status(false).unwrap_err())
+            .contains("bare repository without a working tree"));
+        assert!(repo.grep(&GrepSettings::new(Regex::new("core")?)).is_err());
+        assert!(repo.sync_status(true)?.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn matches_state_checks_current_state() -> Result<()> {
+        let (path, repository, repo) = init_repo()?;
+
+        std::fs::create_dir(path.path().join("conf"))?;
+        commit_file(&repository, "conf/layer.conf", "base")?;
+        let base = repository.head()?.peel_to_commit()?;
+        commit_file(&repository, "file", "master")?;
+
+        repository.branch("release/1.0", &base, false)?;
+        repository.set_head("refs/heads/release/1.0")?;
+        repository.checkout_head(Some(git2::build::CheckoutBuilder::new().force()))?;
+        repository
+            .find_branch("release/1.0", BranchType::Local)?
+            .set_upstream(Some("master"))?;
+        repository.remote("origin", "https://example.com/org/meta-foo.git")?;
+
+        let matches = |filter: StateFilter| repo.matches_state(&filter);
+        let glob = |glob: &str| Some(globset::Glob::new(glob).unwrap().compile_matcher());
+
+        assert!(matches(StateFilter::default())?);
+        assert!(!matches(StateFilter {
+            dirty: true,
+            ..Default::default()
+        })?);
+        assert!(matches(StateFilter {
+            branch: glob("release/*"),
+            remote: Some(Regex::new("example.com/org")?),
+            behind_upstream: true,
+            path: Some(PathBuf::from("conf/layer.conf")),
+            ..Default::default()
+        })?);
+        assert!(!matches(StateFilter {
+            branch: glob("master"),
+            ..Default::default()
+        })?);
+        assert!(!matches(StateFilter {
+            remote: Some(Regex::new("example.com/other")?),
+            ..Default::default()
+        })?);
+        assert!(!matches(StateFilter {
+            path: Some(PathBuf::from("file")),
+            ..Default::default()
+        })?);
+
+        std::fs::write(path.path().join("untracked"), "content")?;
+        repository.set_head_detached(base.id())?;
+
+        assert!(matches(StateFilter {
+            dirty: true,
+            ..Default::default()
+        })?);
+        assert!(!matches(StateFilter {
+            behind_upstream: true,
+            ..Default::default()
+        })?);
+
+        let bare = TempDir::new()?;
+        Repository::init_bare(bare.path())?;
+        let bare = Repo::new(bare.path().to_str().unwrap()).unwrap();
+        assert!(!bare.matches_state(&StateFilter {
+            dirty: true,
+            ..Default::default()
+        })?);
+        assert!(!bare.matches_state(&StateFilter {
+            path: Some(PathBuf::from("conf")),
+            ..Default::default()
+        })?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn grep_searches_working_tree_and_revisions() -> Result<()> {
+        let (path, repository, repo) = init_repo()?;
+
+        std::fs::create_dir(path.path().join("conf"))?;
+        commit_file(&repository, "conf/layer.conf", "LAYERDEPENDS = \"core\"\n")?;
+        commit_file(&repository, "README", "core layer\nnothing else\n")?;
+        repository.tag_lightweight(
+            "v1.0",
+            &repository.head()?.peel(git2::ObjectType::Commit)?,
+            false,
+        )?;
+
+        std::fs::write(path.path().join("README"), "core layer\nand core recipes\n")?;
+        std::fs::write(path.path().join("binary"), b"core\0")?;
+        std::fs::write(path.path().join("untracked"), "core\n")?;
+        let mut index = repository.index()?;
+        index.add_path(Path::new("binary"))?;
+        index.write()?;
+
+        let found = |settings: &GrepSettings| -> Result<Vec<String>> {
+            Ok(repo
+                .grep(settings)?
+                .iter()
+                .map(|found| format!("{}:{}:{}", found.path, found.line, found.text))
+                .collect())
+        };
+
+        let mut settings = GrepSettings::new(Regex::new("core")?);
+        assert_eq!(
+            found(&settings)?,
+            vec![
+                "README:1:core layer",
+                "README:2:and core recipes",
+                "conf/layer.conf:1:LAYERDEPENDS = \"core\"",
+            ]
+        );
+
+        settings.paths = vec![String::from("conf")];
+        assert_eq!(
+            found(&settings)?,
+            vec!["conf/layer.conf:1:LAYERDEPENDS = \"core\""]
+        );
+
+        settings.paths = vec![String::from("READ*")];
+        settings.revision = Some(String::from("v1.0"));
+        assert_eq!(found(&settings)?, vec!["README:1:core layer"]);
+
+        settings.revision = Some(String::from("missing"));
+        assert!(repo.grep(&settings).is_err());
+
+        Ok(())
+    }
+
+    fn commit_file(repository: &Repository, path: &str, content: &str) -> Result<git2::Oid> {
+        let workdir = repository.workdir().unwrap();
+        std::fs::write(workdir.join(path), content)?;
+
+        let mut index = repository.index()?;
+        index.add_path(Path::new(path))?;
+        index.write()?;
+
+        let signature = git2::Signature::now("Jane Doe", "jane@example.com")?;
+        let tree = repository.find_tree(index.write_tree()?)?;
+        let parents = match repository.head() {
+            Ok(head) => vec![head.peel_to_commit()?],
+            Err(_) => Vec::new(),
+        };
+        let parents: Vec<&git2::Commit> = parents.iter().collect();
+
+        Ok(repository.commit(
+            Some("HEAD"),
+            &signature,
+            &signature,
+            &format!("Update {}", path),
+            &tree,
+            &parents,
+        )?)
+    }
+
+    #[test]
+    fn status_reports_file_states() -> Result<()> {
+        let (path, repository, repo) = init_repo()?;
+
+        commit_file(&repository, ".gitignore", "*.log\n")?;
+        commit_file(&repository, "modified", "a")?;
+        commit_file(&repository, "staged", "a")?;
+        commit_file(&repository, "deleted", "a")?;
+        commit_file(&repository, "renamed", "a")?;
+
+        std::fs::write(path.path().join("modified"), "b")?;
+        std::fs::write(path.path().join("staged"), "b")?;
+        std::fs::remove_file(path.path().join("deleted"))?;
+        std::fs::write(path.path().join("new"), "a")?;
+        std::fs::write(path.path().join("build.log"), "a")?;
+
+        repo.custom_cmd(&shell_words::split("add staged")?)?;
+        repo.custom_cmd(&shell_words::split("mv renamed moved")?)?;
+
+        let status = repo.status(false)?;
+        assert_eq!(status.branch, Some(String::from("master")));
+        assert_eq!(status.upstream, None);
+
+        let entries: Vec<(&str, &str, FileState)> = status
+            .entries
+            .iter()
+            .map(|entry| (entry.status.as_str(), entry.path.as_str(), entry.state))
+            .collect();
+
+        assert_eq!(
+            entries,
+            vec![
+                (" D", "deleted", FileState::Tracked),
+                (" M", "modified", FileState::Tracked),
+                ("R ", "renamed -> moved", FileState::Tracked),
+                ("??", "new", FileState::Untracked),
+                ("M ", "staged", FileState::Tracked),
+            ]
+        );
+        assert_eq!(status.entries[0].unstaged, Some(Change::Deleted));
+        assert_eq!(status.entries[2].staged, Some(Change::Renamed));
+        assert!(!status.is_clean());
+
+        let status = repo.status(true)?;
+        assert!(status.entries.iter().any(|entry| entry.status == "!!"
+            && entry.path == "build.log"
+            && entry.state == FileState::Ignored));
+
+        Ok(())
+    }
+
+    #[test]
+    fn status_reports_conflicts_and_upstream_distance() -> Result<()> {
+        let (_path, repository, repo) = init_repo()?;
+
+        commit_file(&repository, "file", "base")?;
+        let base = repository.head()?.peel_to_commit()?;
+        repository.branch("feature", &base, false)?;
+
+        commit_file(&repository, "file", "master")?;
+
+        repository.set_head("refs/heads/feature")?;
+        repository.checkout_head(Some(git2::build::CheckoutBuilder::new().force()))?;
+        commit_file(&repository, "file", "feature")?;
+        commit_file(&repository, "other", "feature")?;
+
+        repository
+            .find_branch("feature", BranchType::Local)?
+            .set_upstream(Some("master"))?;
+
+        let status = repo.status(false)?;
+        assert!(status.is_clean());
+        assert_eq!(status.branch, Some(String::from("feature")));
+        assert_eq!(status.upstream, Some(String::from("master")));
+        assert_eq!((status.ahead, status.behind), (2, 1));
+
+        assert!(repo
+            .custom_cmd(&shell_words::split(
+                "-c user.name=rgit -c user.email=rgit@example.com merge master"
+            )?)
+            .is_err());
+
+        let status = repo.status(false)?;
+        assert_eq!(status.entries.len(), 1);
+        assert_eq!(status.entries[0].status, "UU");
+        assert_eq!(status.entries[0].state, FileState::Conflicted);
+
+        repository.set_head_detached(base.id())?;
+        assert_eq!(repo.status(false)?.branch, None);
+        assert!(repo.sync_status(false)?.is_empty());
+
+        repository.branch("other", &base, false)?;
+        repository
+            .find_branch("other", BranchType::Local)?
+            .set_upstream(Some("master"))?;
+
+        assert_eq!(
+            repo.sync_status(true)?,
+            vec![
+                BranchSync {
+                    branch: String::from("feature"),
+                    upstream: String::from("master"),
+                    ahead: 2,
+                    behind: 1,
+                },
+                BranchSync {
+                    branch: String::from("other"),
+                    upstream: String::from("master"),
+                    ahead: 0,
+                    behind: 1,
+                },
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn fetch_from_local_remote() -> Result<()> {
+        let upstream_dir = TempDir::new()?;
+        let upstream = Repository::init(upstream_dir.path())?;
+        commit_file(&upstream, "file", "a")?;
+
+        let clone_dir = TempDir::new()?;
+        let _ = Repository::clone(upstream_dir.path().to_str().unwrap(), clone_dir.path())?;
+        let repo = Repo::new(clone_dir.path().to_str().unwrap()).unwrap();
+
+        assert!(repo.fetch(&FetchSettings::default())?[0]
+            .updated_refs
+            .is_empty());
+
+        let head = commit_file(&upstream, "file", "b")?;
+        upstream.branch("feature", &upstream.find_commit(head)?, false)?;
+        upstream.tag_lightweight("v1.0", upstream.find_commit(head)?.as_object(), false)?;
+
+        let mut progress = Vec::new();
+        let settings = FetchSettings {
+            tags: FetchTags::None,
+            ..Default::default()
+        };
+        let report = repo.fetch_with_progress(&settings, |fetch| {
+            progress.push((String::from(fetch.remote), fetch.received_objects))
+        })?;
+
+        assert_eq!(report.len(), 1);
+        assert_eq!(report[0].remote, "origin");
+        assert_eq!(report[0].received_objects, 3);
+        assert!(report[0].received_bytes > 0);
+        assert_eq!(progress.last(), Some(&(String::from("origin"), 3)));
+
+        let mut updated: Vec<(&str, bool)> = report[0]
+            .updated_refs
+            .iter()
+            .map(|update| (update.name.as_str(), update.old.is_some()))
+            .collect();
+        updated.sort_unstable();
+        assert_eq!(
+            updated,
+            vec![
+                ("refs/remotes/origin/feature", false),
+                ("refs/remotes/origin/master", true),
+            ]
+        );
+
+        upstream
+            .find_branch("feature", BranchType::Local)?
+            .delete()?;
+
+        let settings = FetchSettings {
+            remotes: vec![String::from("origin")],
+            prune: true,
+            tags: FetchTags::All,
+        };
+        repo.fetch(&settings)?;
+
+        let clone = Repository::open(clone_dir.path())?;
+        assert!(clone.find_reference("refs/tags/v1.0").is_ok());
+        assert!(clone.find_reference("refs/remotes/origin/feature").is_err());
+
+        let settings = FetchSettings {
+            remotes: vec![String::from("missing")],
+            ..Default::default()
+        };
+        assert!(repo.fetch(&settings).is_err());
+
+        Ok(())
+    }
+
+    fn clone_with_user(upstream: &Path) -> Result<(TempDir, Repo)> {
+        let clone_dir = TempDir::new()?;
+        let clone = Repository::clone(upstream.to_str().unwrap(), clone_dir.path())?;
+
+        let mut config = clone.config()?;
+        config.set_str("user.name", "rgit")?;
+        config.set_str("user.email", "rgit@example.com")?;
+
+        let repo = Repo::new(clone_dir.path().to_str().unwrap()).unwrap();
+
+        Ok((clone_dir, repo))
+    }
+
+    #[test]
+    fn update_fast_forwards_clean_repositories() -> Result<()> {
+        let upstream_dir = TempDir::new()?;
+        let upstream = Repository::init(upstream_dir.path())?;
+        commit_file(&upstream, "file", "a")?;
+
+        let (clone_dir, repo) = clone_with_user(upstream_dir.path())?;
+        let clone = Repository::open(clone_dir.path())?;
+
+        let update = repo.update(UpdateStrategy::FastForward)?;
+        assert_eq!(update.outcome, UpdateOutcome::UpToDate);
+        assert_eq!(update.upstream, Some(String::from("origin/master")));
+
+        let head = commit_file(&upstream, "file", "b")?;
+        repo.fetch(&FetchSettings::default())?;
+
+        std::fs::write(clone_dir.path().join("file"), "modified")?;
+        let update = repo.update(UpdateStrategy::FastForward)?;
+        assert_eq!(update.outcome, UpdateOutcome::Dirty);
+        assert!(update.outcome.is_refused());
+        assert_eq!(update.behind, 1);
+        std::fs::write(clone_dir.path().join("file"), "a")?;
+
+        let update = repo.update(UpdateStrategy::FastForward)?;
+        assert_eq!(update.outcome, UpdateOutcome::FastForwarded);
+        assert_eq!(update.new, abbreviate(head));
+        assert_eq!(clone.head()?.target(), Some(head));
+        assert_eq!(std::fs::read_to_string(clone_dir.path().join("file"))?, "b");
+        assert!(repo.status(false)?.is_clean());
+
+        commit_file(&clone, "local", "a")?;
+        let update = repo.update(UpdateStrategy::FastForward)?;
+        assert_eq!(update.outcome, UpdateOutcome::Ahead);
+
+        clone.set_head_detached(head)?;
+        let update = repo.update(UpdateStrategy::FastForward)?;
+        assert_eq!(update.outcome, UpdateOutcome::Detached);
+
+        Ok(())
+    }
+
+    #[test]
+    fn update_ignores_untracked_files() -> Result<()> {
+        let upstream_dir = TempDir::new()?;
+        let upstream = Repository::init(upstream_dir.path())?;
+        commit_file(&upstream, "file", "a")?;
+
+        let (clone_dir, repo) = clone_with_user(upstream_dir.path())?;
+        let clone = Repository::open(clone_dir.path())?;
+
+        let head = commit_file(&upstream, "file", "b")?;
+        repo.fetch(&FetchSettings::default())?;
+
+        std::fs::write(clone_dir.path().join("untracked"), "local")?;
+
+        let update = repo.update(UpdateStrategy::FastForward)?;
+        assert_eq!(update.outcome, UpdateOutcome::FastForwarded);
+        assert_eq!(clone.head()?.target(), Some(head));
+        assert_eq!(
+            std::fs::read_to_string(clone_dir.path().join("untracked"))?,
+            "local"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn update_rebases_diverged_repositories() -> Result<()> {
+        let upstream_dir = TempDir::new()?;
+        let upstream = Repository::init(upstream_dir.path())?;
+        commit_file(&upstream, "file", "a")?;
+
+        let (clone_dir, repo) = clone_with_user(upstream_dir.path())?;
+        let clone = Repository::open(clone_dir.path())?;
+
+        let local = commit_file(&clone, "local", "a")?;
+        let head = commit_file(&upstream, "file", "b")?;
+        repo.fetch(&FetchSettings::default())?;
+
+        let update = repo.update(UpdateStrategy::FastForward)?;
+        assert_eq!(update.outcome, UpdateOutcome::Diverged);
+        assert_eq!((update.ahead, update.behind), (1, 1));
+        assert_eq!(clone.head()?.target(), Some(local));
+
+        let update = repo.update(UpdateStrategy::Rebase)?;
+        assert_eq!(update.outcome, UpdateOutcome::Rebased);
+
+        let rebased = clone.head()?.peel_to_commit()?;
+        assert_eq!(clone.head()?.shorthand(), Some("master"));
+        assert_eq!(rebased.parent_id(0)?, head);
+        assert_eq!(rebased.summary(), Some("Update local"));
+        assert_eq!(std::fs::read_to_string(clone_dir.path().join("file"))?, "b");
+
+        let conflicting = commit_file(&clone, "file", "c")?;
+        commit_file(&upstream, "file", "d")?;
+        repo.fetch(&FetchSettings::default())?;
+
+        let update = repo.update(UpdateStrategy::Rebase)?;
+        assert_eq!(update.outcome, UpdateOutcome::Conflicts);
+        assert_eq!(clone.head()?.target(), Some(conflicting));
+        assert_eq!(clone.state(), git2::RepositoryState::Clean);
+        assert!(repo.status(false)?.is_clean());
+
+        Ok(())
+    }
+
+    #[test]
+    fn cherry_picks_detected_by_trailers_and_patch_id() -> Result<()> {
+        let (_path, repository, repo) = init_repo()?;
+
+        let base = commit_file(&repository, "file", "base")?;
+        let git =
+            |cmd: &str| -> Result<CommandOutput> { repo.custom_cmd(&shell_words::split(cmd)?) };
+
+        let picked_with_trailer = commit_file(&repository, "a", "a")?;
+        let picked = commit_file(&repository, "b", "b")?;
+        commit_file(&repository, "c", "c")?;
+
+        assert!(repo.cherry_picks(None).is_err());
+
+        git(&format!("checkout -q -b release {}", base))?;
+        git(&format!("cherry-pick -x {}", picked_with_trailer))?;
+        git(&format!("cherry-pick {}", picked))?;
+        commit_file(&repository, "d", "d")?;
+        git("checkout -q master")?;
+
+        let cherry_picks = repo.cherry_picks(Some("release"))?;
+        let release = repository.revparse_single("release")?.peel_to_commit()?;
+        let picked_to_release = release.parent(0)?.id();
+        let picked_with_trailer_to_release = release.parent(0)?.parent(0)?.id();
+
+        assert_eq!(
+            cherry_picks,
+            vec![
+                CherryPick {
+                    commit: abbreviate(picked_with_trailer).unwrap(),
+                    summary: String::from("Update a"),
+                    equivalent: abbreviate(picked_with_trailer_to_release).unwrap(),
+                    detected_by: Detection::Trailer,
+                },
+                CherryPick {
+                    commit: abbreviate(picked).unwrap(),
+                    summary: String::from("Update b"),
+                    equivalent: abbreviate(picked_to_release).unwrap(),
+                    detected_by: Detection::PatchId,
+                },
+            ]
+        );
+
+        repository
+            .find_branch("master", BranchType::Local)?
+            .set_upstream(Some("release"))?;
+        assert_eq!(repo.cherry_picks(None)?, cherry_picks);
+
+        assert!(repo.cherry_picks(Some("missing")).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn compare_skips_patch_equivalent_commits() -> Result<()> {
+        let (_path, repository, repo) = init_repo()?;
+
+        let base = commit_file(&repository, "file", "base")?;
+        let git =
+            |cmd: &str| -> Result<CommandOutput> { repo.custom_cmd(&shell_words::split(cmd)?) };
+
+        let picked = commit_file(&repository, "a", "a")?;
+        let picked_with_trailer = commit_file(&repository, "b", "b")?;
+        let missing_in_release = commit_file(&repository, "c", "c")?;
+
+        git(&format!("checkout -q -b release/1.0 {}", base))?;
+        git(&format!("cherry-pick {}", picked))?;
+        git(&format!("cherry-pick -x {}", picked_with_trailer))?;
+        let missing_in_master = commit_file(&repository, "d", "d")?;
+        git("checkout -q master")?;
+
+        let comparison = repo.compare("master", "release/1.0")?;
+
+        assert!(comparison.missing_branches.is_empty());
+        assert_eq!(
+            comparison
+                .missing_in_head
+                .iter()
+                .map(|commit| commit.commit.as_str())
+                .collect::<Vec<_>>(),
+            vec![abbreviate(missing_in_release).unwrap()]
+        );
+        assert_eq!(comparison.missing_in_head[0].summary, "Update c");
+        assert_eq!(comparison.missing_in_head[0].author, "Jane Doe");
+        assert_eq!(
+            comparison
+                .missing_in_base
+                .iter()
+                .map(|commit| commit.commit.as_str())
+                .collect::<Vec<_>>(),
+            vec![abbreviate(missing_in_master).unwrap()]
+        );
+
+        let comparison = repo.compare("master", "release/2.0")?;
+        assert_eq!(
+            comparison.missing_branches,
+            vec![String::from("release/2.0")]
+        );
+        assert!(comparison.missing_in_base.is_empty());
+        assert!(comparison.missing_in_head.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn history_filters_commits() -> Result<()> {
+        let (path, repository, repo) = init_repo()?;
+
+        assert!(repo
+            .history(&HistoryFilter::new(LogRange::last(10)))?
+            .is_empty());
+
+        commit_file(&repository, "README", "a")?;
+        std::fs::create_dir(path.path().join("conf"))?;
+        commit_file(&repository, "conf/local.conf", "a")?;
+        commit_file(&repository, "README", "b")?;
+
+        let summaries = |filter: &HistoryFilter| -> Result<Vec<String>> {
+            Ok(repo
+                .history(filter)?
+                .into_iter()
+                .map(|commit| commit.summary)
+                .collect())
+        };
+
+        assert_eq!(
+            summaries(&HistoryFilter::new(LogRange::last(2)))?,
+            vec!["Update README", "Update conf/local.conf"]
+        );
+
+        let mut filter = HistoryFilter::new(LogRange::last(10));
+        filter.paths = vec![String::from("conf")];
+        assert_eq!(summaries(&filter)?, vec!["Update conf/local.conf"]);
+
+        let mut filter = HistoryFilter::new(LogRange::last(10));
+        filter.message = Some(regex::Regex::new("^Update R")?);
+        filter.author = Some(AuthorMatcher::new(&[String::from("jane@example")])?);
+        assert_eq!(summaries(&filter)?, vec!["Update README", "Update README"]);
+
+        filter.author = Some(AuthorMatcher::new(&[String::from("^John")])?);
+        assert!(summaries(&filter)?.is_empty());
+
+        let now = chrono::Local::now().timestamp();
+        let filter = HistoryFilter::new(LogRange {
+            number: 1,
+            after: Some(now - 3600),
+            before: None,
+        });
+        assert_eq!(summaries(&filter)?.len(), 3);
+
+        let filter = HistoryFilter::new(LogRange {
+            number: 1,
+            after: None,
+            before: Some(now - 3600),
+        });
+        assert!(summaries(&filter)?.is_empty());
+
+        Ok(())
     }
 
     #[test]
@@ -1,25 +1,382 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
+use chrono::{Local, TimeZone};
+use globset::GlobMatcher;
+use regex::Regex;
+
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use super::report::{
+    BranchSync, CherryPick, CommandOutput, CommitMatch, CommitSummary, Comparison, GrepMatch,
+    RemoteFetch, StatusReport, SubmoduleState, UpdateReport,
+};
+
+/// Describes which commits should be taken into account by operations looking through git log
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LogRange {
+    /// Number of last commits to look into, ignored when `after` or `before` is set
+    pub number: u32,
+    /// Only commits newer than this unix timestamp
+    pub after: Option<i64>,
+    /// Only commits older than this unix timestamp
+    pub before: Option<i64>,
+}
+
+impl LogRange {
+    /// Creates range covering last `number` of commits
+    pub fn last(number: u32) -> LogRange {
+        LogRange {
+            number,
+            after: None,
+            before: None,
+        }
+    }
+
+    /// Returns true if range is limited by dates instead of number of commits
+    pub fn is_date_range(&self) -> bool {
+        self.after.is_some() || self.before.is_some()
+    }
+}
+
+impl fmt::Display for LogRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let format_date = |timestamp: i64| Local.timestamp(timestamp, 0).format("%Y-%m-%d %H:%M");
+
+        match (self.after, self.before) {
+            (None, None) => write!(f, "in last {} commits", self.number),
+            (Some(after), None) => write!(f, "after {}", format_date(after)),
+            (None, Some(before)) => write!(f, "before {}", format_date(before)),
+            (Some(after), Some(before)) => write!(
+                f,
+                "between {} and {}",
+                format_date(after),
+                format_date(before)
+            ),
+        }
+    }
+}
+
+/// Matches identities of commit authors and committers
+///
+/// Every author is a regex matched against name or email, e.g. `Jane Doe`, `@example.com$`.
+#[derive(Debug, Clone)]
+pub struct AuthorMatcher {
+    authors: Vec<Regex>,
+}
+
+impl AuthorMatcher {
+    /// Creates matcher matching any of `authors`
+    ///
+    /// # Arguments
+    ///
+    /// * `authors` - regexes matched against name or email
+    pub fn new(authors: &[String]) -> Result<AuthorMatcher> {
+        let authors = authors
+            .iter()
+            .map(|author| Regex::new(author).context(format!("Invalid author regex: {}", author)))
+            .collect::<Result<Vec<Regex>>>()?;
+
+        Ok(AuthorMatcher { authors })
+    }
+
+    /// Creates matcher matching exactly given names and emails, e.g. `user.name` and `user.email`
+    /// from git config, empty identities are skipped
+    ///
+    /// # Arguments
+    ///
+    /// * `identities` - names or emails
+    pub fn exact(identities: &[String]) -> Result<AuthorMatcher> {
+        let authors: Vec<String> = identities
+            .iter()
+            .filter(|identity| !identity.is_empty())
+            .map(|identity| format!("^{}$", regex::escape(identity)))
+            .collect();
+
+        if authors.is_empty() {
+            anyhow::bail!("No author to look for");
+        }
+
+        AuthorMatcher::new(&authors)
+    }
+
+    /// Returns true if any of authors matches `name` or `email`
+    pub fn is_match(&self, name: &str, email: &str) -> bool {
+        self.authors
+            .iter()
+            .any(|author| author.is_match(name) || author.is_match(email))
+    }
+}
+
+impl fmt::Display for AuthorMatcher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let authors: Vec<&str> = self.authors.iter().map(|author| author.as_str()).collect();
+
+        write!(f, "{}", authors.join(", "))
+    }
+}
+
+/// Describes which commits are listed in log of repositories
+#[derive(Debug, Clone)]
+pub struct HistoryFilter {
+    /// Commits to look into
+    pub range: LogRange,
+    /// Only commits with author or committer matching any of authors
+    pub author: Option<AuthorMatcher>,
+    /// Only commits changing any of the paths, all commits if empty
+    pub paths: Vec<String>,
+    /// Only commits with message matching the regex
+    pub message: Option<Regex>,
+}
+
+impl HistoryFilter {
+    /// Creates filter accepting all commits in the `range`
+    pub fn new(range: LogRange) -> HistoryFilter {
+        HistoryFilter {
+            range,
+            author: None,
+            paths: Vec::new(),
+            message: None,
+        }
+    }
+}
+
+/// Describes which tags are downloaded by fetch
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FetchTags {
+    /// Tags pointing at downloaded commits, default behavior of `git fetch`
+    Auto,
+    /// All tags from the remote, like `git fetch --tags`
+    All,
+    /// No tags, like `git fetch --no-tags`
+    None,
+}
+
+impl FromStr for FetchTags {
+    type Err = anyhow::Error;
+
+    fn from_str(tags: &str) -> Result<Self> {
+        match tags {
+            "auto" => Ok(FetchTags::Auto),
+            "all" => Ok(FetchTags::All),
+            "none" => Ok(FetchTags::None),
+            _ => anyhow::bail!("Unknown tags option: {}", tags),
+        }
+    }
+}
+
+/// Describes what is fetched from remotes
+#[derive(Debug, Clone, PartialEq)]
+pub struct FetchSettings {
+    /// Names of fetched remotes, all configured remotes if empty
+    pub remotes: Vec<String>,
+    /// Removes remote tracking branches which no longer exist on the remote
+    pub prune: bool,
+    /// Tags downloaded from the remote
+    pub tags: FetchTags,
+}
+
+impl Default for FetchSettings {
+    fn default() -> Self {
+        FetchSettings {
+            remotes: Vec::new(),
+            prune: false,
+            tags: FetchTags::Auto,
+        }
+    }
+}
+
+/// Describes how current branch is updated to its upstream
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UpdateStrategy {
+    /// Only fast-forward, refuse to update diverged branches, like `git pull --ff-only`
+    FastForward,
+    /// Rebase commits missing in upstream onto it, like `git pull --rebase`
+    Rebase,
+}
+
+/// Describes what is searched for in tracked files
+#[derive(Debug, Clone)]
+pub struct GrepSettings {
+    /// Lines matching the regex are reported
+    pub pattern: Regex,
+    /// Revision which files are searched, files in the working tree if not given
+    pub revision: Option<String>,
+    /// Only files matching any of the pathspecs are searched, all files if empty
+    pub paths: Vec<String>,
+}
+
+impl GrepSettings {
+    /// Creates settings searching all files in the working tree
+    pub fn new(pattern: Regex) -> GrepSettings {
+        GrepSettings {
+            pattern,
+            revision: None,
+            paths: Vec::new(),
+        }
+    }
+}
+
+/// Predicates on the current state of repositories, all of them have to be satisfied
+#[derive(Debug, Clone, Default)]
+pub struct StateFilter {
+    /// Working tree has staged, unstaged, conflicted or untracked files
+    pub dirty: bool,
+    /// Current branch matches the glob, repositories with detached HEAD never match
+    pub branch: Option<GlobMatcher>,
+    /// URL of any remote matches the regex
+    pub remote: Option<Regex>,
+    /// Current branch is behind its upstream
+    pub behind_upstream: bool,
+    /// Path exists in the working tree, or in HEAD of bare repositories
+    pub path: Option<PathBuf>,
+}
+
+impl StateFilter {
+    /// Checks if any predicate is set
+    pub fn is_empty(&self) -> bool {
+        !self.dirty
+            && self.branch.is_none()
+            && self.remote.is_none()
+            && !self.behind_upstream
+            && self.path.is_none()
+    }
+}
 
 /// Trait describing interface for available operations on repositories
+///
+/// Operations don't print anything, they return typed results which can be presented by
+/// `presenter` module or used directly by other tools. Operations are executed on all repositories
+/// with `Repositories::execute`.
+///
+/// # Examples
+///
+/// ```
+/// use git2::Repository;
+/// use rgit::exec::repo::Repo;
+/// use rgit::exec::repo_operations::RepoOperations;
+/// use tempfile::TempDir;
+///
+/// let path = TempDir::new().unwrap();
+/// let _ = Repository::init(path.path()).unwrap();
+/// let repo = Repo::new(path.path().to_str().unwrap()).unwrap();
+///
+/// assert!(repo.status(false).unwrap().is_clean());
+/// assert!(repo.cherry_picks(None).unwrap().is_empty());
+/// ```
 pub trait RepoOperations {
-    /// Executing custom git command on a repository
+    /// Executing custom git command on a repository and capturing its output
+    ///
+    /// # Arguments
+    ///
+    /// * `args` - arguments passed to git, e.g. `["status", "--porcelain"]`
+    fn custom_cmd(&self, args: &[String]) -> Result<CommandOutput>;
+    /// Returns status of the working tree, similar to `git status --porcelain --branch`
+    ///
+    /// # Arguments
+    ///
+    /// * `ignored` - report also files ignored by git
+    fn status(&self, ignored: bool) -> Result<StatusReport>;
+    /// Returns number of commits ahead and behind upstream for branches with configured upstream
+    ///
+    /// # Arguments
+    ///
+    /// * `all_branches` - check every local branch, not only the current one
+    fn sync_status(&self, all_branches: bool) -> Result<Vec<BranchSync>>;
+    /// Returns state of every submodule, similar to `git submodule status`
+    fn submodule_status(&self) -> Result<Vec<SubmoduleState>>;
+    /// Fetches remotes selected in `settings`, similar to `git fetch`
+    ///
+    /// # Arguments
+    ///
+    /// * `settings` - remotes to fetch and fetch options
+    fn fetch(&self, settings: &FetchSettings) -> Result<Vec<RemoteFetch>>;
+    /// Updates current branch to its upstream without fetching it
+    ///
+    /// Update is refused if there are any changes in the working tree or, unless rebasing,
+    /// when the branch and its upstream have diverged.
+    ///
+    /// # Arguments
+    ///
+    /// * `strategy` - how the branch is updated
+    fn update(&self, strategy: UpdateStrategy) -> Result<UpdateReport>;
+    /// Finds commits present both on the checked out branch and on the `base`, similar to `git cherry`
+    ///
+    /// Commits are considered the same if one of them has `(cherry picked from commit ...)` trailer
+    /// pointing at the other one or if they introduce the same changes, i.e. have the same patch-id.
+    /// Empty if there are no cherry-picks or no base to compare with.
+    ///
+    /// # Arguments
+    ///
+    /// * `base` - revision the branch is compared with, upstream of the branch if not given
+    fn cherry_picks(&self, base: Option<&str>) -> Result<Vec<CherryPick>>;
+    /// Finds commits of each branch missing on the other one
+    ///
+    /// Commits with the same changes or cherry-picked with `-x` from a commit on the other branch are
+    /// treated as present on both branches. Merge commits are not compared.
+    ///
+    /// # Arguments
+    ///
+    /// * `base` - revision of the first compared branch, e.g. `main`
+    /// * `head` - revision of the second compared branch, e.g. `release/1.0`
+    fn compare(&self, base: &str, head: &str) -> Result<Comparison>;
+    /// Lists commits of checked out branch matching the `filter`, newest first
+    ///
+    /// When `filter` range isn't limited by dates, at most `range.number` of matching commits is listed.
+    ///
+    /// # Arguments
+    ///
+    /// * `filter` - describes listed commits
+    fn history(&self, filter: &HistoryFilter) -> Result<Vec<CommitSummary>>;
+    /// Finds lines of tracked files matching the pattern, similar to `git grep`
+    ///
+    /// Binary files and submodules are skipped.
+    ///
+    /// # Arguments
+    ///
+    /// * `settings` - searched pattern, revision and files
+    fn grep(&self, settings: &GrepSettings) -> Result<Vec<GrepMatch>>;
+    /// Finds commits authored or committed by any of `authors` in given `range` of commits,
+    /// empty if there are none
+    ///
+    /// Identities are resolved with `.mailmap` of the repository before matching.
     ///
     /// # Arguments
     ///
-    /// * `cmd` - command to execute, e.g. status --porcelain
-    fn custom_cmd(&self, cmd: String) -> Result<()>;
-    /// Executing `git status --porcelain` on the repository and displaying result if it's not clean.
-    /// It doesn't display anything on a clean repository.
-    fn porcelain(&self) -> Result<()>;
-    /// Finds repositories which have cherry-picks in history
-    fn find_cherry_picks(&self) -> Result<Option<String>>;
-    /// Prints all cherry picks found in history
-    fn print_cherry_picks(&self) -> Result<()>;
-    /// Print repository and commits with author if there is one in last `number` of commits
+    /// * `range` - number of the newest matching commits or dates of commits to look into
+    /// * `authors` - authors to look for
+    fn commits_with_author(
+        &self,
+        range: &LogRange,
+        authors: &AuthorMatcher,
+    ) -> Result<Vec<CommitMatch>>;
+    /// Checks if the current state of the repository satisfies all predicates of `filter`
     ///
     /// # Arguments
     ///
-    /// * `number` - last number of commits to look into
-    /// * `author` - author to look for
-    fn print_commits_with_author(&self, number: u32, author: &str) -> Result<()>;
+    /// * `filter` - checked predicates
+    fn matches_state(&self, filter: &StateFilter) -> Result<bool>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn author_matcher_matches_name_or_email() {
+        let matcher =
+            AuthorMatcher::new(&[String::from("^Jane"), String::from("@example.org$")]).unwrap();
+
+        assert!(matcher.is_match("Jane Doe", "jane@example.com"));
+        assert!(matcher.is_match("John Doe", "john@example.org"));
+        assert!(!matcher.is_match("John Doe", "john@example.com"));
+        assert!(AuthorMatcher::new(&[String::from("(")]).is_err());
+
+        let matcher = AuthorMatcher::exact(&[String::from("J. Doe"), String::from("")]).unwrap();
+        assert!(matcher.is_match("J. Doe", "jane@example.com"));
+        assert!(!matcher.is_match("JX Doe", "jane@example.com"));
+        assert!(!matcher.is_match("J. Doe Jr", "jane@example.com"));
+        assert_eq!(matcher.to_string(), "^J\\. Doe$");
+        assert!(AuthorMatcher::exact(&[String::new()]).is_err());
+    }
 }
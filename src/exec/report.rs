@@ -0,0 +1,567 @@
+use serde::{Deserialize, Serialize};
+
+use std::fmt;
+
+/// Output of a command executed on a repository
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct CommandOutput {
+    /// Exit code of the command, `None` if it was terminated by a signal
+    pub exit_code: Option<i32>,
+    /// Captured standard output, empty when output was not captured
+    pub stdout: String,
+    /// Captured standard error, empty when output was not captured
+    pub stderr: String,
+}
+
+/// Error returned when a command executed on a repository exits with non-zero status
+#[derive(Debug)]
+pub struct CommandFailed {
+    /// Executed command
+    pub cmd: String,
+    /// Exit code and output of the command
+    pub output: CommandOutput,
+}
+
+impl fmt::Display for CommandFailed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.output.exit_code {
+            Some(code) => write!(f, "`{}` exited with code {}", self.cmd, code),
+            None => write!(f, "`{}` was terminated by a signal", self.cmd),
+        }
+    }
+}
+
+impl std::error::Error for CommandFailed {}
+
+/// Kind of change of a single file
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Change {
+    Added,
+    Modified,
+    Deleted,
+    Renamed,
+    TypeChanged,
+}
+
+impl Change {
+    /// Returns letter used by `git status --porcelain` for the change
+    pub fn code(&self) -> char {
+        match self {
+            Change::Added => 'A',
+            Change::Modified => 'M',
+            Change::Deleted => 'D',
+            Change::Renamed => 'R',
+            Change::TypeChanged => 'T',
+        }
+    }
+}
+
+/// State of a single file reported by status
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum FileState {
+    /// File known to git with staged and/or unstaged changes
+    Tracked,
+    /// File not known to git
+    Untracked,
+    /// File with merge conflicts
+    Conflicted,
+    /// File ignored by git, reported only on request
+    Ignored,
+}
+
+/// Single entry of repository status
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct StatusEntry {
+    /// Two letter status code as printed by `git status --porcelain`, e.g. ` M` or `??`
+    pub status: String,
+    /// Path of the file, `old -> new` for renamed files
+    pub path: String,
+    /// State of the file
+    pub state: FileState,
+    /// Change staged in the index
+    pub staged: Option<Change>,
+    /// Change in the working tree not staged yet
+    pub unstaged: Option<Change>,
+}
+
+/// Status of the working tree of a repository
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct StatusReport {
+    /// Name of the current branch, `None` if HEAD is detached
+    pub branch: Option<String>,
+    /// Name of the upstream of the current branch, e.g. `origin/master`
+    pub upstream: Option<String>,
+    /// Number of commits on the current branch missing in upstream
+    pub ahead: usize,
+    /// Number of commits in upstream missing on the current branch
+    pub behind: usize,
+    /// Changed, untracked, conflicted and requested ignored files
+    pub entries: Vec<StatusEntry>,
+}
+
+impl StatusReport {
+    /// Returns true if there are no changes in the repository, ignored files are not changes
+    pub fn is_clean(&self) -> bool {
+        self.entries
+            .iter()
+            .all(|entry| entry.state == FileState::Ignored)
+    }
+
+    /// Returns true if there are staged, modified or conflicted files known to git, untracked
+    /// and ignored files are not counted
+    pub fn has_tracked_changes(&self) -> bool {
+        self.entries
+            .iter()
+            .any(|entry| entry.state == FileState::Tracked || entry.state == FileState::Conflicted)
+    }
+}
+
+/// Distance between a local branch and its upstream
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct BranchSync {
+    /// Name of the local branch
+    pub branch: String,
+    /// Name of the upstream of the branch, e.g. `origin/master`
+    pub upstream: String,
+    /// Number of commits on the branch missing in upstream
+    pub ahead: usize,
+    /// Number of commits in upstream missing on the branch
+    pub behind: usize,
+}
+
+impl BranchSync {
+    /// Returns true if the branch and its upstream point at the same commit
+    pub fn is_in_sync(&self) -> bool {
+        self.ahead == 0 && self.behind == 0
+    }
+}
+
+/// State of a submodule compared with the commit recorded in its superproject
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct SubmoduleState {
+    /// Path of the submodule relative to the superproject
+    pub path: String,
+    /// Abbreviated commit recorded in the index of the superproject, `None` if it isn't recorded yet
+    pub recorded: Option<String>,
+    /// Abbreviated commit checked out in the submodule, `None` if it isn't initialized
+    pub checked_out: Option<String>,
+    /// Submodule isn't cloned into the working tree of the superproject
+    pub uninitialized: bool,
+    /// Submodule has changed or untracked files
+    pub dirty: bool,
+}
+
+impl SubmoduleState {
+    /// Returns true if the submodule is initialized, clean and checked out at the recorded commit
+    pub fn is_clean(&self) -> bool {
+        !self.uninitialized && !self.dirty && self.recorded == self.checked_out
+    }
+}
+
+/// Outcome of updating current branch to its upstream
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum UpdateOutcome {
+    /// Branch already points at its upstream
+    UpToDate,
+    /// Branch has only commits missing in upstream, nothing to update
+    Ahead,
+    /// Branch was fast-forwarded to its upstream
+    FastForwarded,
+    /// Commits of the branch were rebased onto its upstream
+    Rebased,
+    /// HEAD is detached, so there is no branch to update
+    Detached,
+    /// Branch has no upstream configured
+    NoUpstream,
+    /// Update refused because of staged, modified or conflicted files in the working tree
+    Dirty,
+    /// Update refused because branch and its upstream have diverged
+    Diverged,
+    /// Rebase was aborted because of conflicts
+    Conflicts,
+}
+
+impl UpdateOutcome {
+    /// Returns true if update was needed but it was refused
+    pub fn is_refused(&self) -> bool {
+        matches!(
+            self,
+            UpdateOutcome::Dirty | UpdateOutcome::Diverged | UpdateOutcome::Conflicts
+        )
+    }
+}
+
+/// Result of updating current branch to its upstream
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct UpdateReport {
+    /// Name of the current branch, `None` if HEAD is detached
+    pub branch: Option<String>,
+    /// Name of the upstream of the current branch, e.g. `origin/master`
+    pub upstream: Option<String>,
+    /// Number of commits on the branch missing in upstream before update
+    pub ahead: usize,
+    /// Number of commits in upstream missing on the branch before update
+    pub behind: usize,
+    /// What happened with the branch
+    pub outcome: UpdateOutcome,
+    /// Abbreviated hash the branch pointed at before update, `None` if it wasn't updated
+    pub old: Option<String>,
+    /// Abbreviated hash the branch points at after update, `None` if it wasn't updated
+    pub new: Option<String>,
+}
+
+/// Reference updated by fetch
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct RefUpdate {
+    /// Full name of the reference, e.g. `refs/remotes/origin/master`
+    pub name: String,
+    /// Abbreviated hash the reference pointed at, `None` for new references
+    pub old: Option<String>,
+    /// Abbreviated hash the reference points at, `None` for pruned references
+    pub new: Option<String>,
+}
+
+/// Result of fetching a single remote
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct RemoteFetch {
+    /// Name of the remote
+    pub remote: String,
+    /// Number of objects received from the remote
+    pub received_objects: usize,
+    /// Number of objects in the pack sent by the remote
+    pub total_objects: usize,
+    /// Number of bytes received from the remote
+    pub received_bytes: usize,
+    /// References updated by the fetch, empty if everything was up to date
+    pub updated_refs: Vec<RefUpdate>,
+}
+
+/// Progress of a fetch in progress, reported while objects are downloaded
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FetchProgress<'a> {
+    /// Name of the fetched remote
+    pub remote: &'a str,
+    /// Number of objects received so far
+    pub received_objects: usize,
+    /// Number of objects in the pack sent by the remote
+    pub total_objects: usize,
+    /// Number of bytes received so far
+    pub received_bytes: usize,
+}
+
+/// How cherry-pick was detected
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Detection {
+    /// Commit message contains `(cherry picked from commit ...)` trailer added by `git cherry-pick -x`
+    Trailer,
+    /// Both commits introduce the same changes, like `git cherry` detects them
+    PatchId,
+}
+
+/// Commit present both on the checked out branch and on the base it is compared with
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct CherryPick {
+    /// Abbreviated hash of the commit on the checked out branch
+    pub commit: String,
+    /// First line of the commit message
+    pub summary: String,
+    /// Abbreviated hash of the equivalent commit on the base
+    pub equivalent: String,
+    /// How the equivalence was detected
+    pub detected_by: Detection,
+}
+
+/// Basic information about a commit
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct CommitSummary {
+    /// Abbreviated hash of the commit
+    pub commit: String,
+    /// First line of the commit message
+    pub summary: String,
+    /// Name of the author
+    pub author: String,
+    /// Email of the author
+    pub email: String,
+    /// Commit time as a unix timestamp
+    pub time: i64,
+}
+
+/// Commit of unified log of all repositories
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct LogEntry {
+    /// Name of the repository the commit belongs to
+    pub repository: String,
+    /// Path to the repository
+    pub path: String,
+    /// The commit
+    #[serde(flatten)]
+    pub commit: CommitSummary,
+}
+
+/// Commits missing on each of two compared branches
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct Comparison {
+    /// Branch expected to contain all commits of `head`
+    pub base: String,
+    /// Branch compared with `base`
+    pub head: String,
+    /// Compared branches which don't exist in the repository, nothing is compared if not empty
+    pub missing_branches: Vec<String>,
+    /// Commits on `head` missing on `base`
+    pub missing_in_base: Vec<CommitSummary>,
+    /// Commits on `base` missing on `head`
+    pub missing_in_head: Vec<CommitSummary>,
+}
+
+/// Commit matching searched author
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct CommitMatch {
+    /// Abbreviated hash of the commit
+    pub commit: String,
+    /// Names of refs pointing at the commit, e.g. `HEAD -> master, origin/master`
+    pub refs: String,
+    /// First line of the commit message
+    pub summary: String,
+    /// Name of the author
+    pub author: String,
+    /// Email of the author
+    pub email: String,
+    /// Commit time as a unix timestamp
+    pub time: i64,
+}
+
+/// Kind of a repository
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum RepoKind {
+    /// Repository with its main working tree
+    #[default]
+    Normal,
+    /// Linked working tree of another repository, created by `git worktree add`
+    Worktree,
+    /// Repository without a working tree
+    Bare,
+}
+
+/// Line of a tracked file matching searched pattern
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct GrepMatch {
+    /// Path of the file relative to the repository
+    pub path: String,
+    /// Number of the matching line, starting from 1
+    pub line: usize,
+    /// Content of the matching line
+    pub text: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exec::presenter::{
+        comparison_text, fetch_text, grep_text, status_text, submodule_status_text,
+        sync_status_text, update_text,
+    };
+    use crate::exec::test_utils::init_repo;
+    use chrono::Local;
+    use termion::color;
+
+    #[test]
+    fn status_text_test() {
+        let status = StatusReport {
+            branch: Some(String::from("master")),
+            entries: vec![StatusEntry {
+                status: String::from("MM"),
+                path: String::from("file"),
+                state: FileState::Tracked,
+                staged: Some(Change::Modified),
+                unstaged: Some(Change::Modified),
+            }],
+            ..Default::default()
+        };
+
+        let (_path, _, repo) = init_repo().unwrap();
+
+        assert!(status_text(&repo, &status).ends_with("## master\nMM file\n\n"));
+        assert!(status_text(&repo, &StatusReport::default()).is_empty());
+    }
+
+    #[test]
+    fn sync_status_text_lists_only_out_of_sync_branches() {
+        let (_path, _, repo) = init_repo().unwrap();
+
+        let mut branches = vec![BranchSync {
+            branch: String::from("master"),
+            upstream: String::from("origin/master"),
+            ahead: 0,
+            behind: 0,
+        }];
+        assert!(sync_status_text(&repo, &branches).is_empty());
+
+        branches.push(BranchSync {
+            branch: String::from("feature"),
+            upstream: String::from("origin/feature"),
+            ahead: 0,
+            behind: 3,
+        });
+        assert!(sync_status_text(&repo, &branches)
+            .ends_with("\n## feature...origin/feature [behind 3]\n\n"));
+    }
+
+    #[test]
+    fn submodule_status_text_lists_only_changed_submodules() {
+        let (_path, _, repo) = init_repo().unwrap();
+
+        let clean = SubmoduleState {
+            path: String::from("sources/poky"),
+            recorded: Some(String::from("1234567")),
+            checked_out: Some(String::from("1234567")),
+            uninitialized: false,
+            dirty: false,
+        };
+        assert!(submodule_status_text(&repo, std::slice::from_ref(&clean)).is_empty());
+
+        let moved = SubmoduleState {
+            path: String::from("sources/meta-foo"),
+            checked_out: Some(String::from("89abcde")),
+            dirty: true,
+            ..clean.clone()
+        };
+        let uninitialized = SubmoduleState {
+            path: String::from("sources/meta-bar"),
+            checked_out: None,
+            uninitialized: true,
+            ..clean.clone()
+        };
+        assert!(submodule_status_text(&repo, &[clean, moved, uninitialized]).ends_with(
+            "sources/meta-foo: checked out 89abcde, recorded 1234567, dirty\nsources/meta-bar: not initialized\n\n"
+        ));
+    }
+
+    #[test]
+    fn fetch_text_lists_updated_refs() {
+        let (_path, _, repo) = init_repo().unwrap();
+
+        let mut remotes = vec![RemoteFetch {
+            remote: String::from("origin"),
+            ..Default::default()
+        }];
+        assert!(fetch_text(&repo, &remotes).is_empty());
+
+        remotes[0].received_objects = 3;
+        remotes[0].total_objects = 3;
+        remotes[0].received_bytes = 300;
+        remotes[0].updated_refs = vec![
+            RefUpdate {
+                name: String::from("refs/remotes/origin/master"),
+                old: Some(String::from("1234567")),
+                new: Some(String::from("89abcde")),
+            },
+            RefUpdate {
+                name: String::from("refs/tags/v1.0"),
+                old: None,
+                new: Some(String::from("89abcde")),
+            },
+        ];
+
+        assert!(fetch_text(&repo, &remotes).ends_with(
+            "From origin: received 3/3 objects, 300 bytes\n   \
+             1234567..89abcde  origin/master\n \
+             * [new]             tags/v1.0\n\n"
+        ));
+    }
+
+    #[test]
+    fn update_text_reports_updates_and_refusals() {
+        let (_path, _, repo) = init_repo().unwrap();
+
+        let mut update = UpdateReport {
+            branch: Some(String::from("master")),
+            upstream: Some(String::from("origin/master")),
+            ahead: 0,
+            behind: 0,
+            outcome: UpdateOutcome::UpToDate,
+            old: None,
+            new: None,
+        };
+        assert!(update_text(&repo, &update).is_empty());
+
+        update.behind = 2;
+        update.outcome = UpdateOutcome::FastForwarded;
+        update.old = Some(String::from("1234567"));
+        update.new = Some(String::from("89abcde"));
+        assert!(update_text(&repo, &update)
+            .ends_with("master...origin/master: fast-forwarded 1234567..89abcde\n\n"));
+
+        update.ahead = 1;
+        update.outcome = UpdateOutcome::Diverged;
+        update.old = None;
+        update.new = None;
+        assert!(update_text(&repo, &update).ends_with(
+            "master...origin/master [ahead 1, behind 2]: refused, branches have diverged, use --rebase\n\n"
+        ));
+    }
+
+    #[test]
+    fn comparison_text_lists_missing_commits_and_branches() {
+        let (_path, _, repo) = init_repo().unwrap();
+
+        let mut comparison = Comparison {
+            base: String::from("main"),
+            head: String::from("release/1.0"),
+            ..Default::default()
+        };
+        assert!(comparison_text(&repo, &comparison).is_empty());
+
+        comparison.missing_in_head.push(CommitSummary {
+            commit: String::from("1234567"),
+            summary: String::from("Fix build"),
+            author: String::from("Jane Doe"),
+            email: String::from("jane@example.com"),
+            time: Local::now().timestamp(),
+        });
+        assert!(comparison_text(&repo, &comparison).ends_with(
+            "missing in release/1.0 (1):\n* 1234567 Fix build (0 seconds ago) <Jane Doe>\n\n"
+        ));
+
+        let comparison = Comparison {
+            base: String::from("main"),
+            head: String::from("release/1.0"),
+            missing_branches: vec![String::from("release/1.0")],
+            ..Default::default()
+        };
+        assert!(
+            comparison_text(&repo, &comparison).ends_with("branch release/1.0 doesn't exist\n\n")
+        );
+    }
+
+    #[test]
+    fn grep_text_prefixes_matches_with_repository() {
+        let (path, _, repo) = init_repo().unwrap();
+
+        assert!(grep_text(&repo, &[]).is_empty());
+
+        let text = grep_text(
+            &repo,
+            &[GrepMatch {
+                path: String::from("conf/layer.conf"),
+                line: 3,
+                text: String::from("BBFILE_COLLECTIONS += \"foo\""),
+            }],
+        );
+        assert_eq!(
+            text,
+            format!(
+                "{}{}{}:{}3{}:BBFILE_COLLECTIONS += \"foo\"\n",
+                color::Fg(color::Magenta),
+                path.path().join("conf/layer.conf").display(),
+                color::Reset.fg_str(),
+                color::Fg(color::Green),
+                color::Reset.fg_str()
+            )
+        );
+    }
+}
@@ -1,106 +1,212 @@
-use super::{repo::Repo, repo_operations::RepoOperations};
+use super::{
+    pool,
+    repo::Repo,
+    repo_operations::{RepoOperations, StateFilter},
+};
 use anyhow::{Context, Result};
-use log::debug;
-use termion::color;
+use log::{debug, warn};
+
+use std::path::PathBuf;
+
+/// Result of an operation executed on a single repository
+#[derive(Debug)]
+pub struct RepoResult<T> {
+    /// Path to the repository
+    pub path: PathBuf,
+    /// Result of the operation
+    pub result: Result<T>,
+}
 
 /// Struct describing all repositories `rgit` is working on
 pub struct Repositories {
     pub repos: Vec<Repo>,
+    /// Number of repositories processed in parallel
+    pub jobs: usize,
+    /// Continue with other repositories when operation fails on one of them
+    pub keep_going: bool,
+}
+
+impl Default for Repositories {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Repositories {
     /// Creates new instance of `Repositories`
     pub fn new() -> Repositories {
-        Repositories { repos: Vec::new() }
+        Repositories {
+            repos: Vec::new(),
+            jobs: 1,
+            keep_going: false,
+        }
     }
-    /// Pretty prints title of executed command
-    fn print_title(&self, title: &str) {
-        print!(
-            "{}\n{}{}{}\n",
-            color::Fg(color::Red),
-            title,
-            color::Reset.fg_str(),
-            color::Reset.bg_str()
-        );
+    /// Executes `operation` on all repositories using `jobs` threads
+    ///
+    /// `inspect` is called in the calling thread for every result, in the order of repositories,
+    /// as soon as the result is available, e.g. to print it.
+    /// In `keep_going` mode failed results are returned together with successful ones,
+    /// otherwise the first failure is returned as an error.
+    ///
+    /// # Arguments
+    ///
+    /// * `operation` - executed for every repository together with its index
+    /// * `inspect` - called for every result
+    pub fn execute<T, F, I>(&self, operation: F, mut inspect: I) -> Result<Vec<RepoResult<T>>>
+    where
+        T: Send,
+        F: Fn(usize, &Repo) -> Result<T> + Sync,
+        I: FnMut(&Repo, &Result<T>) -> Result<()>,
+    {
+        let indexed: Vec<(usize, &Repo)> = self.repos.iter().enumerate().collect();
+        let mut repos = self.repos.iter();
+        let mut results = Vec::new();
+
+        let operation = |&(index, repo): &(usize, &Repo)| operation(index, repo);
+
+        pool::for_each_ordered(&indexed, self.jobs, operation, |result| {
+            let repo = repos.next().context("Missing repository for result")?;
+
+            inspect(repo, &result)?;
+
+            let result = match result {
+                Err(err) if !self.keep_going => return Err(err),
+                result => result,
+            };
+
+            if let Err(err) = &result {
+                debug!("Operation failed on {}: {:#}", repo.path().display(), err);
+            }
+
+            results.push(RepoResult {
+                path: repo.path().to_path_buf(),
+                result,
+            });
+
+            Ok(())
+        })?;
+
+        Ok(results)
     }
-}
-
-impl RepoOperations for Repositories {
-    /// Executes custom git command on all repos
+    /// Keeps only repositories which current state satisfies `filter`
+    ///
+    /// In `keep_going` mode repositories which couldn't be checked are dropped with a warning,
+    /// otherwise the first failure is returned as an error.
     ///
     /// # Arguments
     ///
-    /// * `cmd` - git command to execute
-    fn custom_cmd(&self, cmd: String) -> Result<()> {
-        debug!("Executing command: {} on all repositories", cmd);
+    /// * `filter` - checked predicates
+    pub fn retain_matching(&mut self, filter: &StateFilter) -> Result<()> {
+        let mut results = self
+            .execute(|_, repo| repo.matches_state(filter), |_, _| Ok(()))?
+            .into_iter();
+
+        self.repos.retain(|_| match results.next() {
+            Some(RepoResult {
+                result: Ok(matches),
+                ..
+            }) => matches,
+            Some(RepoResult {
+                path,
+                result: Err(err),
+            }) => {
+                warn!("Skipping {}: {:#}", path.display(), err);
+                false
+            }
+            None => false,
+        });
+
+        debug!("{} repositories match {:?}", self.repos.len(), filter);
 
-        self.print_title(&format!("git {}", cmd));
+        Ok(())
+    }
+}
 
-        for repo in &self.repos {
-            repo.custom_cmd(String::from(&cmd))
-                .context("Failed to execute command on repo")?;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exec::report::CommandFailed;
+    use crate::exec::test_utils::init_repo;
+    use tempfile::TempDir;
+
+    fn repositories_with_failing_repo() -> Result<(Vec<TempDir>, Repositories)> {
+        let mut dirs = Vec::new();
+        let mut repositories = Repositories::new();
+
+        for _ in 0..3 {
+            let (dir, _, repo) = init_repo()?;
+            repositories.repos.push(repo);
+            dirs.push(dir);
         }
-        Ok(())
+
+        // Removing repository after creating `Repo` makes every command on it fail
+        std::fs::remove_dir_all(dirs[1].path().join(".git"))?;
+
+        Ok((dirs, repositories))
     }
-    /// Executes `git status --porcelain` on all repositories
-    fn porcelain(&self) -> Result<()> {
-        debug!("Executing git status --porcelain on all repositories");
 
-        self.print_title("git status --porcelain");
+    #[test]
+    fn custom_cmd_stops_on_failure() -> Result<()> {
+        let (_dirs, repositories) = repositories_with_failing_repo()?;
 
-        for repo in &self.repos {
-            repo.porcelain()
-                .context("Failed to execute porcelain command")?;
-        }
+        assert!(repositories
+            .execute(
+                |_, repo| repo.custom_cmd(&[String::from("status")]),
+                |_, _| Ok(()),
+            )
+            .is_err());
 
         Ok(())
     }
-    /// Finds repositories which have cherry-picks in history
-    fn find_cherry_picks(&self) -> Result<Option<String>> {
-        debug!("Trying to find all repositories which have cherry-picks in history");
 
-        self.print_title("repositories with cherry-picks in git reflog");
+    #[test]
+    fn retain_matching_drops_failed_repositories_in_keep_going() -> Result<()> {
+        let (dirs, mut repositories) = repositories_with_failing_repo()?;
+        std::fs::write(dirs[2].path().join("new"), "content")?;
 
-        for repo in &self.repos {
-            repo.find_cherry_picks()
-                .context("Failed to find cherry picks")?;
-        }
+        let filter = StateFilter {
+            dirty: true,
+            ..Default::default()
+        };
 
-        Ok(None)
-    }
-    /// Prints all cherry picks found in history
-    fn print_cherry_picks(&self) -> Result<()> {
-        debug!("Prints all cherry picks found in history");
+        assert!(repositories.retain_matching(&filter).is_err());
+        assert_eq!(repositories.repos.len(), 3);
 
-        self.print_title("repositories with cherry-picks in git reflog");
+        repositories.keep_going = true;
+        repositories.retain_matching(&filter)?;
 
-        for repo in &self.repos {
-            repo.print_cherry_picks()
-                .context("Failed to print cherry picks")?;
-        }
+        assert_eq!(repositories.repos.len(), 1);
+        assert_eq!(repositories.repos[0].path(), dirs[2].path());
 
         Ok(())
     }
-    /// Print repositories for which there is an author in last `number` of commits
-    ///
-    /// # Arguments
-    ///
-    /// * `number` - last number of commits to look into
-    /// * `author` - author to look for
-    fn print_commits_with_author(&self, number: u32, author: &str) -> Result<()> {
-        debug!(
-            "Prints all repositories which have author in last {} commits",
-            number
-        );
-
-        self.print_title(&format!(
-            "repositories with author {} in last {} commits",
-            author, number
-        ));
-
-        for repo in &self.repos {
-            repo.print_commits_with_author(number, author)
-                .context("Failed to print commits with author")?;
+
+    #[test]
+    fn custom_cmd_keep_going_returns_failures() -> Result<()> {
+        let (dirs, mut repositories) = repositories_with_failing_repo()?;
+        repositories.keep_going = true;
+
+        for jobs in &[1, 3] {
+            repositories.jobs = *jobs;
+
+            let results = repositories.execute(
+                |_, repo| repo.custom_cmd(&[String::from("status")]),
+                |_, _| Ok(()),
+            )?;
+
+            assert_eq!(results.len(), 3);
+            assert!(results[0].result.is_ok());
+            assert!(results[2].result.is_ok());
+            assert_eq!(results[1].path, dirs[1].path());
+
+            let err = results[1].result.as_ref().unwrap_err();
+            assert_eq!(
+                err.downcast_ref::<CommandFailed>()
+                    .unwrap()
+                    .output
+                    .exit_code,
+                Some(128)
+            );
         }
 
         Ok(())
@@ -0,0 +1,172 @@
+use anyhow::Result;
+
+use std::iter::Peekable;
+use std::str::{Chars, FromStr};
+
+/// Boolean expression of group or tag names selecting repositories, e.g. `bsp & !vendor | tools`
+///
+/// Names can be combined with `&` (and), `|` (or), `!` (not) and parentheses, `!` binds the
+/// strongest and `|` the weakest.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Selector {
+    Name(String),
+    Not(Box<Selector>),
+    And(Box<Selector>, Box<Selector>),
+    Or(Box<Selector>, Box<Selector>),
+}
+
+impl Selector {
+    /// Checks if labels of the repository satisfy the expression
+    ///
+    /// # Arguments
+    ///
+    /// * `labels` - groups or tags of the repository
+    pub fn matches(&self, labels: &[String]) -> bool {
+        match self {
+            Selector::Name(name) => labels.iter().any(|label| label == name),
+            Selector::Not(selector) => !selector.matches(labels),
+            Selector::And(left, right) => left.matches(labels) && right.matches(labels),
+            Selector::Or(left, right) => left.matches(labels) || right.matches(labels),
+        }
+    }
+}
+
+impl FromStr for Selector {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut parser = Parser {
+            chars: s.chars().peekable(),
+        };
+
+        let selector = parser.or()?;
+
+        match parser.next_token() {
+            None => Ok(selector),
+            Some(c) => anyhow::bail!("Unexpected '{}' in expression '{}'", c, s),
+        }
+    }
+}
+
+/// Recursive descent parser of selector expressions
+struct Parser<'a> {
+    chars: Peekable<Chars<'a>>,
+}
+
+impl Parser<'_> {
+    fn or(&mut self) -> Result<Selector> {
+        let mut selector = self.and()?;
+
+        while self.next_token() == Some('|') {
+            self.chars.next();
+            selector = Selector::Or(Box::new(selector), Box::new(self.and()?));
+        }
+
+        Ok(selector)
+    }
+
+    fn and(&mut self) -> Result<Selector> {
+        let mut selector = self.not()?;
+
+        while self.next_token() == Some('&') {
+            self.chars.next();
+            selector = Selector::And(Box::new(selector), Box::new(self.not()?));
+        }
+
+        Ok(selector)
+    }
+
+    fn not(&mut self) -> Result<Selector> {
+        match self.next_token() {
+            Some('!') => {
+                self.chars.next();
+                Ok(Selector::Not(Box::new(self.not()?)))
+            }
+            Some('(') => {
+                self.chars.next();
+                let selector = self.or()?;
+                match self.next_token() {
+                    Some(')') => {
+                        self.chars.next();
+                        Ok(selector)
+                    }
+                    _ => anyhow::bail!("Missing ')'"),
+                }
+            }
+            _ => self.name(),
+        }
+    }
+
+    fn name(&mut self) -> Result<Selector> {
+        let mut name = String::new();
+
+        while let Some(&c) = self.chars.peek() {
+            if c.is_whitespace() || "&|!()".contains(c) {
+                break;
+            }
+            name.push(c);
+            self.chars.next();
+        }
+
+        if name.is_empty() {
+            match self.next_token() {
+                Some(c) => anyhow::bail!("Expected name, found '{}'", c),
+                None => anyhow::bail!("Expected name, found end of expression"),
+            }
+        }
+
+        Ok(Selector::Name(name))
+    }
+
+    /// Skips whitespace and returns the next character without consuming it
+    fn next_token(&mut self) -> Option<char> {
+        while self.chars.next_if(|c| c.is_whitespace()).is_some() {}
+        self.chars.peek().copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn labels(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn selector_parsing_test() -> Result<()> {
+        let name = |n: &str| Box::new(Selector::Name(String::from(n)));
+
+        assert_eq!("bsp".parse::<Selector>()?, *name("bsp"));
+        assert_eq!(
+            "a | b & !c".parse::<Selector>()?,
+            Selector::Or(
+                name("a"),
+                Box::new(Selector::And(name("b"), Box::new(Selector::Not(name("c")))))
+            )
+        );
+        assert_eq!(
+            "(a|b)&c".parse::<Selector>()?,
+            Selector::And(Box::new(Selector::Or(name("a"), name("b"))), name("c"))
+        );
+
+        assert!("".parse::<Selector>().is_err());
+        assert!("a &".parse::<Selector>().is_err());
+        assert!("(a | b".parse::<Selector>().is_err());
+        assert!("a b".parse::<Selector>().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn selector_matching_test() -> Result<()> {
+        let selector: Selector = "bsp-layers & !vendor | tools".parse()?;
+
+        assert!(selector.matches(&labels(&["bsp-layers"])));
+        assert!(selector.matches(&labels(&["vendor", "tools"])));
+        assert!(!selector.matches(&labels(&["bsp-layers", "vendor"])));
+        assert!(!selector.matches(&labels(&[])));
+
+        Ok(())
+    }
+}
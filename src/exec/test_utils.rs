@@ -0,0 +1,21 @@
+use anyhow::Result;
+use git2::Repository;
+use tempfile::TempDir;
+
+use super::repo::Repo;
+
+/// Creates a repository in a temporary directory, with user configured so git commands can commit
+///
+/// Directory is removed when returned `TempDir` is dropped.
+pub fn init_repo() -> Result<(TempDir, Repository, Repo)> {
+    let path = TempDir::new()?;
+    let repository = Repository::init(path.path())?;
+
+    let mut config = repository.config()?;
+    config.set_str("user.name", "rgit")?;
+    config.set_str("user.email", "rgit@example.com")?;
+
+    let repo = Repo::new(path.path().to_str().unwrap()).unwrap();
+
+    Ok((path, repository, repo))
+}
@@ -0,0 +1,96 @@
+use anyhow::{Context, Result};
+use log::debug;
+use regex::RegexBuilder;
+
+use crate::exec::exec::read_repositories;
+use crate::exec::presenter::{self, Operation, Presenter};
+use crate::exec::repo_operations::{GrepSettings, RepoOperations};
+use crate::options::opts::GrepOpts;
+
+/// Starts `grep` command
+///
+/// Returns exit code of the process, non-zero if search failed on any of the repositories.
+///
+/// # Arguments
+///
+/// * `opts` - options from command line
+pub fn run(opts: &GrepOpts) -> Result<i32> {
+    debug!("ENTER grep run: {:?}", opts);
+
+    let mut repositories = read_repositories(&opts.source_file)?;
+
+    repositories.jobs = opts.jobs;
+    repositories.keep_going = opts.keep_going;
+
+    let settings = get_grep_settings(opts)?;
+
+    let title = match &settings.revision {
+        Some(revision) => format!("lines matching {} at {}", opts.pattern, revision),
+        None => format!("lines matching {}", opts.pattern),
+    };
+
+    let mut presenter = Presenter::new(opts.format);
+
+    let result = presenter
+        .run(
+            &repositories,
+            &Operation {
+                name: "grep",
+                title,
+                field: "matches",
+                streamed: false,
+                worktree: settings.revision.is_none(),
+            },
+            |_, repo| repo.grep(&settings),
+            |repo, matches| presenter::grep_text(repo, matches),
+        )
+        .context("Failed to search repositories");
+
+    presenter.finish(result)
+}
+
+/// Creates settings of the search from command line options
+fn get_grep_settings(opts: &GrepOpts) -> Result<GrepSettings> {
+    let pattern = RegexBuilder::new(&opts.pattern)
+        .case_insensitive(opts.ignore_case)
+        .build()
+        .context(format!("Invalid pattern: {}", opts.pattern))?;
+
+    Ok(GrepSettings {
+        pattern,
+        revision: opts.revision.clone(),
+        paths: opts.paths.clone(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Clap;
+
+    #[test]
+    fn get_grep_settings_test() -> Result<()> {
+        let opts = GrepOpts::try_parse_from(vec![
+            "grep",
+            "-i",
+            "linux-yocto",
+            "--revision",
+            "origin/master",
+            "--",
+            "*.bbappend",
+            "conf/",
+        ])?;
+        let settings = get_grep_settings(&opts)?;
+
+        assert!(settings
+            .pattern
+            .is_match("PREFERRED_PROVIDER = \"Linux-Yocto\""));
+        assert_eq!(settings.revision, Some(String::from("origin/master")));
+        assert_eq!(settings.paths, vec!["*.bbappend", "conf/"]);
+
+        let opts = GrepOpts::try_parse_from(vec!["grep", "("])?;
+        assert!(get_grep_settings(&opts).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,136 @@
+use anyhow::{Context, Result};
+use log::debug;
+
+use std::path::PathBuf;
+
+use crate::options::opts::LabelAction;
+use crate::scan::scan_file::{Label, ScanFile};
+
+/// Starts `group` or `tag` command
+///
+/// # Arguments
+///
+/// * `label` - kind of labels managed by the command
+/// * `action` - action from command line
+/// * `source_file` - file with scan results, default file is used if not given
+pub fn run(label: Label, action: &LabelAction, source_file: &Option<String>) -> Result<()> {
+    debug!("ENTER {} run: {:?}", label, action);
+
+    let path = match source_file {
+        Some(path) => PathBuf::from(path),
+        None => crate::tools::get_default_scan_path().context("Failed to get default path")?,
+    };
+
+    let mut file = ScanFile::read(&path)?;
+
+    match action {
+        LabelAction::Add { name, repos } => {
+            file.add_label(label, name, repos)?;
+            file.write(&path)?;
+        }
+        LabelAction::Remove { name, repos } => {
+            file.remove_label(label, name, repos)?;
+            file.write(&path)?;
+        }
+        LabelAction::List { name } => print!("{}", labels_text(&file, label, name)),
+    }
+
+    Ok(())
+}
+
+/// Lists groups or tags with paths of their repositories
+///
+/// # Arguments
+///
+/// * `file` - scan results
+/// * `label` - kind of listed labels
+/// * `name` - only repositories of this group or tag are listed if given
+fn labels_text(file: &ScanFile, label: Label, name: &Option<String>) -> String {
+    let mut text = String::new();
+
+    for (label_name, entries) in file.labels(label) {
+        match name {
+            Some(name) if name != label_name => continue,
+            Some(_) => {}
+            None => text += &format!("{}\n", label_name),
+        }
+
+        for entry in entries {
+            match name {
+                Some(_) => text += &format!("{}\n", entry.path.display()),
+                None => text += &format!("  {}\n", entry.path.display()),
+            }
+        }
+    }
+
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scan::scan_file::{ScanEntry, ScanRoot};
+    use std::path::Path;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn group_add_remove_and_list() -> Result<()> {
+        let temp = NamedTempFile::new()?;
+        let source_file = Some(temp.path().to_str().unwrap().to_string());
+
+        ScanFile::new(
+            ScanRoot::new(Path::new("/work")),
+            vec![
+                ScanEntry::new(Path::new("/work/meta-foo")),
+                ScanEntry::new(Path::new("/work/meta-bar")),
+            ],
+        )
+        .write(temp.path())?;
+
+        let add = |name: &str, repos: &[&str]| LabelAction::Add {
+            name: String::from(name),
+            repos: repos.iter().map(|repo| repo.to_string()).collect(),
+        };
+
+        run(
+            Label::Group,
+            &add("bsp", &["meta-foo", "meta-bar"]),
+            &source_file,
+        )?;
+        run(
+            Label::Group,
+            &add("tools", &["/work/meta-bar"]),
+            &source_file,
+        )?;
+        run(Label::Tag, &add("vendor", &["meta-foo"]), &source_file)?;
+        assert!(run(Label::Group, &add("bsp", &["missing"]), &source_file).is_err());
+
+        let file = ScanFile::read(temp.path())?;
+        assert_eq!(
+            labels_text(&file, Label::Group, &None),
+            "bsp\n  /work/meta-foo\n  /work/meta-bar\ntools\n  /work/meta-bar\n"
+        );
+        assert_eq!(
+            labels_text(&file, Label::Tag, &Some(String::from("vendor"))),
+            "/work/meta-foo\n"
+        );
+
+        run(
+            Label::Group,
+            &LabelAction::Remove {
+                name: String::from("bsp"),
+                repos: Vec::new(),
+            },
+            &source_file,
+        )?;
+
+        let file = ScanFile::read(temp.path())?;
+        assert_eq!(
+            labels_text(&file, Label::Group, &None),
+            "tools\n  /work/meta-bar\n"
+        );
+        assert_eq!(file.repositories[0].tags, vec!["vendor"]);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,164 @@
+use anyhow::{Context, Result};
+use log::debug;
+
+use std::cmp::Reverse;
+
+use crate::exec::exec::read_repositories;
+use crate::exec::presenter::{self, Operation, OutputFormat, Presenter};
+use crate::exec::repo_operations::{AuthorMatcher, HistoryFilter, LogRange, RepoOperations};
+use crate::exec::report::{CommitSummary, LogEntry};
+use crate::exec::repositories::RepoResult;
+use crate::options::opts::LogOpts;
+use crate::tools::parse_date;
+
+/// Starts `log` command
+///
+/// Prints commits of all repositories merged into a single timeline, newest first.
+/// Returns exit code of the process, non-zero if log couldn't be read from any of the repositories.
+///
+/// # Arguments
+///
+/// * `opts` - options from command line
+pub fn run(opts: &LogOpts) -> Result<i32> {
+    debug!("ENTER log run: {:?}", opts);
+
+    let mut repositories = read_repositories(&opts.source_file)?;
+
+    repositories.jobs = opts.jobs;
+    repositories.keep_going = opts.keep_going;
+
+    let filter = get_history_filter(opts)?;
+
+    let mut presenter = Presenter::new(opts.format);
+
+    let operation = Operation {
+        name: "log",
+        title: format!("commits of all repositories {}", filter.range),
+        field: "commits",
+        streamed: false,
+        worktree: false,
+    };
+
+    if presenter.format == OutputFormat::Text {
+        presenter::print_title(&operation.title);
+    }
+
+    let results = repositories.execute(
+        |_, repo| Ok((repo.name(), repo.history(&filter)?)),
+        |repo, result| match result {
+            Ok(_) => Ok(()),
+            Err(_) => presenter.present(&operation, repo, result, &|_, _| String::new()),
+        },
+    );
+
+    let result = match results {
+        Ok(results) => merge_history(results, &filter.range)
+            .iter()
+            .try_for_each(|entry| presenter.present_entry(entry, presenter::log_entry_text(entry))),
+        Err(err) => Err(err),
+    };
+
+    presenter.finish(result.context("Failed to print log of repositories"))
+}
+
+/// Merges commits of all repositories into a single list sorted by commit time, newest first
+///
+/// When `range` isn't limited by dates, only `range.number` of the newest commits is returned.
+fn merge_history(
+    results: Vec<RepoResult<(String, Vec<CommitSummary>)>>,
+    range: &LogRange,
+) -> Vec<LogEntry> {
+    let mut entries: Vec<LogEntry> = results
+        .into_iter()
+        .filter_map(|result| {
+            let path = result.path.display().to_string();
+            let (name, commits) = result.result.ok()?;
+
+            Some(commits.into_iter().map(move |commit| LogEntry {
+                repository: name.clone(),
+                path: path.clone(),
+                commit,
+            }))
+        })
+        .flatten()
+        .collect();
+
+    entries.sort_by_key(|entry| Reverse(entry.commit.time));
+
+    if !range.is_date_range() {
+        entries.truncate(range.number as usize);
+    }
+
+    entries
+}
+
+/// Creates filter of listed commits from command line options
+fn get_history_filter(opts: &LogOpts) -> Result<HistoryFilter> {
+    let parse = |date: &Option<String>| -> Result<Option<i64>> {
+        match date {
+            Some(date) => Ok(Some(
+                parse_date(date).context(format!("Invalid date: {}", date))?,
+            )),
+            None => Ok(None),
+        }
+    };
+
+    Ok(HistoryFilter {
+        range: LogRange {
+            number: opts.number,
+            after: parse(&opts.after)?,
+            before: parse(&opts.before)?,
+        },
+        author: match opts.author.is_empty() {
+            true => None,
+            false => Some(AuthorMatcher::new(&opts.author)?),
+        },
+        paths: opts.path.clone(),
+        message: opts.grep.clone(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn commit(commit: &str, time: i64) -> CommitSummary {
+        CommitSummary {
+            commit: String::from(commit),
+            summary: String::new(),
+            author: String::new(),
+            email: String::new(),
+            time,
+        }
+    }
+
+    #[test]
+    fn merge_history_sorts_commits_of_all_repositories() {
+        let results = vec![
+            RepoResult {
+                path: PathBuf::from("/layers/a"),
+                result: Ok((String::from("a"), vec![commit("a2", 20), commit("a1", 5)])),
+            },
+            RepoResult {
+                path: PathBuf::from("/layers/b"),
+                result: Err(anyhow::anyhow!("Failed")),
+            },
+            RepoResult {
+                path: PathBuf::from("/layers/c"),
+                result: Ok((String::from("c"), vec![commit("c2", 30), commit("c1", 10)])),
+            },
+        ];
+
+        let entries = merge_history(results, &LogRange::last(3));
+
+        assert_eq!(
+            entries
+                .iter()
+                .map(|entry| (entry.repository.as_str(), entry.commit.commit.as_str()))
+                .collect::<Vec<_>>(),
+            vec![("c", "c2"), ("a", "a2"), ("c", "c1")]
+        );
+        assert_eq!(entries[1].path, "/layers/a");
+    }
+}
@@ -1,4 +1,9 @@
+pub mod compare;
 pub mod exec;
+pub mod grep;
+pub mod group;
+pub mod history;
+pub mod list;
 pub mod logging;
 pub mod options;
 pub mod scan;
@@ -9,25 +14,57 @@ use clap::Clap;
 use log::trace;
 
 use options::opts::{Opts, SubCommand};
+use scan::scan_file::Label;
 
-pub fn run() -> Result<()> {
+/// Runs rgit with options from command line and returns exit code of the process
+pub fn run() -> Result<i32> {
     let options: Opts = Opts::parse();
 
     logging::init_logging(options.verbosity).context("Failed to initialize logging")?;
 
     trace!("ENTER run");
 
-    match options.subcmd {
+    let exit_code = match options.subcmd {
         SubCommand::Scan(opts) => {
             trace!("scan");
             scan::scan::run(&opts).context("Failed to run scan")?;
+            0
         }
         SubCommand::Exec(opts) => {
             trace!("exec");
-            exec::exec::run(&opts).context("Failed to run exec")?;
+            exec::exec::run(&opts).context("Failed to run exec")?
         }
-    }
+        SubCommand::Compare(opts) => {
+            trace!("compare");
+            compare::compare::run(&opts).context("Failed to run compare")?
+        }
+        SubCommand::Log(opts) => {
+            trace!("log");
+            history::history::run(&opts).context("Failed to run log")?
+        }
+        SubCommand::Grep(opts) => {
+            trace!("grep");
+            grep::grep::run(&opts).context("Failed to run grep")?
+        }
+        SubCommand::List(opts) => {
+            trace!("list");
+            list::list::run(&opts).context("Failed to run list")?;
+            0
+        }
+        SubCommand::Group(opts) => {
+            trace!("group");
+            group::group::run(Label::Group, &opts.action, &opts.source_file)
+                .context("Failed to run group")?;
+            0
+        }
+        SubCommand::Tag(opts) => {
+            trace!("tag");
+            group::group::run(Label::Tag, &opts.action, &opts.source_file)
+                .context("Failed to run tag")?;
+            0
+        }
+    };
 
     trace!("EXIT run");
-    Ok(())
+    Ok(exit_code)
 }
@@ -0,0 +1,89 @@
+use anyhow::{Context, Result};
+use git2::Repository;
+use log::debug;
+
+use std::path::PathBuf;
+
+use crate::options::opts::ListOpts;
+use crate::scan::scan::print_paths_to_stdout;
+use crate::scan::scan_file::{ScanEntry, ScanFile};
+
+/// Starts `list` command
+///
+/// # Arguments
+///
+/// * `opts` - options from command line
+pub fn run(opts: &ListOpts) -> Result<()> {
+    debug!("ENTER list run: {:?}", opts);
+
+    let path = match &opts.source_file {
+        Some(path) => PathBuf::from(path),
+        None => crate::tools::get_default_scan_path().context("Failed to get default path")?,
+    };
+
+    let mut file = ScanFile::read(&path)?;
+
+    if opts.prune {
+        let removed = prune(&mut file);
+
+        if !removed.is_empty() {
+            file.write(&path)?;
+        }
+
+        for entry in removed {
+            eprintln!("Removed {}", entry.path.display());
+        }
+    }
+
+    print_paths_to_stdout(file.repositories, opts.format)
+}
+
+/// Removes repositories which paths no longer hold a git repository and returns them
+///
+/// Relative paths are looked for in directories they were computed against, so running it from
+/// another directory doesn't remove them.
+fn prune(file: &mut ScanFile) -> Vec<ScanEntry> {
+    file.retain(|path| Repository::open(path).is_ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scan::scan_file::ScanRoot;
+    use tempfile::TempDir;
+
+    #[test]
+    fn prune_removes_missing_repositories() -> Result<()> {
+        let root = TempDir::new()?;
+        for dir in &["meta-foo", "meta-bar", "not-a-repo"] {
+            std::fs::create_dir(root.path().join(dir))?;
+        }
+        let _ = Repository::init(root.path().join("meta-foo"))?;
+
+        let mut scan_root = ScanRoot::new(root.path());
+        scan_root.settings.relative = true;
+
+        let mut file = ScanFile::new(
+            scan_root,
+            vec![
+                ScanEntry::new(&root.path().join("meta-foo")),
+                ScanEntry::new(&root.path().join("meta-bar")),
+                ScanEntry::new(&root.path().join("removed")),
+                ScanEntry::new(std::path::Path::new("meta-foo")),
+            ],
+        );
+
+        let removed: Vec<PathBuf> = prune(&mut file)
+            .into_iter()
+            .map(|entry| entry.path)
+            .collect();
+
+        assert_eq!(
+            removed,
+            vec![root.path().join("meta-bar"), root.path().join("removed")]
+        );
+        assert_eq!(file.repositories.len(), 2);
+
+        Ok(())
+    }
+}
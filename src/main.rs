@@ -1,7 +1,7 @@
 use anyhow::{Context, Result};
 
 fn main() -> Result<()> {
-    rgit::run().context("Error while running rgit")?;
+    let exit_code = rgit::run().context("Error while running rgit")?;
 
-    Ok(())
+    std::process::exit(exit_code);
 }
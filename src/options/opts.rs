@@ -1,4 +1,13 @@
 use clap::{AppSettings, Clap};
+use globset::Glob;
+
+use crate::exec::presenter::OutputFormat;
+use crate::exec::repo_operations::FetchTags;
+use crate::exec::selector::Selector;
+use crate::scan::scan::ScanPath;
+use regex::Regex;
+
+use std::path::PathBuf;
 
 /// rgit allows you to control multiple git repositories at the same time.
 /// It is a bit different from `repo` tool since, it does not require  initialization and can work with only selected repositories.
@@ -19,15 +28,34 @@ pub struct Opts {
 }
 
 #[derive(Clap)]
+#[allow(clippy::large_enum_variant)]
 pub enum SubCommand {
     Scan(ScanOpts),
     Exec(ExecOpts),
+    Compare(CompareOpts),
+    Log(LogOpts),
+    Grep(GrepOpts),
+    List(ListOpts),
+    Group(GroupOpts),
+    Tag(TagOpts),
 }
 
 /// Scans repositories in subdirectories
+///
+/// EXAMPLES:
+///
+/// rgit scan
+///
+/// rgit scan ~/src ~/yocto:1..4 /opt/bsp:2 --relative --base ~ -s
 #[derive(Clap, Debug)]
 #[clap(setting = AppSettings::ColoredHelp)]
 pub struct ScanOpts {
+    /// [Optional] Directory to scan, can be used multiple times, default: current directory
+    ///
+    /// Depth limits of a directory can be given after `:`, either max depth, e.g. `~/src:2`, or min
+    /// and max depth, e.g. `/opt/bsp:1..4`, `--max-depth` and `--min-depth` are used otherwise
+    #[clap(short, long = "root", number_of_values = 1)]
+    pub roots: Vec<ScanPath>,
     /// [Optional] Max depth of subdirectories to scan
     ///
     /// Defines how many levels of directories to scan in search for git repositories
@@ -35,7 +63,7 @@ pub struct ScanOpts {
     pub max_depth: usize,
     /// [Optional] Min depth of subdirectories to scan
     ///
-    /// Defines on which level of directory to start scanning, 0 means current working directory
+    /// Defines on which level of directory to start scanning, 0 means the scanned directory itself
     #[clap(long, default_value = "0")]
     pub min_depth: usize,
     /// [Optional] Save output to the specified file for later use by rgit
@@ -43,11 +71,55 @@ pub struct ScanOpts {
     /// Saves scan results to the file to be used by other rgit commands, default: ~/.rgit
     #[clap(short, long)]
     pub save_to_file: Option<Option<String>>,
+    /// [Optional] Adds results to repositories already saved by `--save-to-file` instead of replacing them
+    ///
+    /// Repositories found again are updated, e.g. `rgit scan -s --append` run in `~/work/a` and then
+    /// in `~/work/b` saves repositories of both directories
+    #[clap(long, requires = "save-to-file")]
+    pub append: bool,
+    /// [Optional] Scans again all directories recorded in the file given by `--save-to-file`
+    ///
+    /// Every directory is scanned with the options used to scan it before, other options are ignored.
+    /// Repositories which are no longer found are removed, groups and tags of the others are kept
+    #[clap(long, requires = "save-to-file", conflicts_with = "append")]
+    pub refresh: bool,
     /// [Optional] Generate relative paths instead of absolute ones
     ///
-    /// Prints relative paths of repositories
+    /// Prints paths of repositories relative to `--base`, repositories outside of it keep absolute paths
     #[clap(long)]
     pub relative: bool,
+    /// [Optional] Directory `--relative` paths are relative to, default: current directory
+    ///
+    /// Relative paths saved in the file are read relative to the base by other rgit commands, so it
+    /// can be used only with `--save-to-file`
+    #[clap(long, requires_all = &["relative", "save-to-file"])]
+    pub base: Option<PathBuf>,
+    /// [Optional] Looks for repositories also inside found repositories
+    ///
+    /// By default directories of found repositories aren't scanned any further
+    #[clap(long)]
+    pub nested: bool,
+    /// [Optional] Lists initialized submodules of found repositories as well
+    ///
+    /// Submodules are listed right after their superproject, records of `--format json` contain `parent`
+    #[clap(long)]
+    pub submodules: bool,
+    /// [Optional] Lists linked worktrees of found repositories as well, also outside of scanned directory
+    #[clap(long)]
+    pub worktrees: bool,
+    /// [Optional] Skips directories matching the glob, can be used multiple times
+    ///
+    /// Globs use gitignore syntax and are relative to the scanned directory, e.g. `node_modules`, `build/tmp`.
+    /// Rules can also be saved in `.rgitignore` files at any level of scanned directories
+    #[clap(long, number_of_values = 1)]
+    pub exclude: Vec<String>,
+    /// [Optional] Format of printed results: text, json or ndjson, default: text
+    ///
+    /// Text lists paths of repositories, records of json formats contain `path`, `parent`, path to
+    /// the superproject of a submodule, and `kind`: normal, worktree or bare. Results saved in the file
+    /// are not affected
+    #[clap(long, default_value = "text", possible_values = &["text", "json", "ndjson"])]
+    pub format: OutputFormat,
 }
 
 /// Executes git commands in specified repositories
@@ -59,6 +131,32 @@ pub struct ScanOpts {
 /// rgit scan --relative | rgit exec --print-cherry-picks
 ///
 /// rgit exec -s --print-cherry-picks --porcelain --with-author
+///
+/// rgit exec -s -j 8 -c fetch
+///
+/// rgit exec -s --keep-going -c "pull --ff-only"
+///
+/// rgit exec -s -- log --oneline -5
+///
+/// rgit exec -s --porcelain --format ndjson
+///
+/// rgit exec -s --sync-status --all-branches
+///
+/// rgit exec -s -j 8 --fetch --prune --remote origin
+///
+/// rgit exec -s --submodule-status
+///
+/// rgit exec -s --fetch --update --rebase
+///
+/// rgit exec -s --sh 'echo "$RGIT_REPO_INDEX $RGIT_REPO_NAME" && cargo update'
+///
+/// rgit exec -s --with-author --after 2026-10-01 --before "1 week ago"
+///
+/// rgit exec --with-author "^Jane" "@example.com$" -n 50 -s
+///
+/// rgit exec -s --group "bsp-layers | tools" --tag '!vendor' --fetch
+///
+/// rgit exec -s --on-branch "release/*" --only-dirty --porcelain
 #[derive(Clap, Debug)]
 #[clap(setting = AppSettings::ColoredHelp)]
 pub struct ExecOpts {
@@ -67,29 +165,393 @@ pub struct ExecOpts {
     /// Reads results from the file instead of stdin, default: ~/.rgit
     #[clap(short, long)]
     pub source_file: Option<Option<String>>,
+    /// [Optional] Executes operations only on repositories in groups matching the expression,
+    /// can be used multiple times
+    ///
+    /// Group names can be combined with `&` (and), `|` (or), `!` (not) and parentheses,
+    /// e.g. `bsp-layers & !legacy`. Groups are assigned with `rgit group add`
+    #[clap(long, number_of_values = 1, requires = "source-file")]
+    pub group: Vec<Selector>,
+    /// [Optional] Executes operations only on repositories with tags matching the expression,
+    /// can be used multiple times
+    ///
+    /// Tag names can be combined like in `--group`, e.g. `vendor | upstream`. Tags are assigned
+    /// with `rgit tag add`
+    #[clap(long, number_of_values = 1, requires = "source-file")]
+    pub tag: Vec<Selector>,
+    /// [Optional] Executes operations only on repositories with staged, unstaged, conflicted or
+    /// untracked files
+    #[clap(long)]
+    pub only_dirty: bool,
+    /// [Optional] Executes operations only on repositories with current branch matching the glob,
+    /// e.g. `release/*`
+    #[clap(long)]
+    pub on_branch: Option<Glob>,
+    /// [Optional] Executes operations only on repositories with URL of any remote matching the regex,
+    /// e.g. `github.com/org`
+    #[clap(long)]
+    pub remote_matches: Option<Regex>,
+    /// [Optional] Executes operations only on repositories with current branch behind its upstream
+    ///
+    /// Upstreams are not fetched, the state of remote tracking branches from the last fetch is used
+    #[clap(long)]
+    pub behind_upstream: bool,
+    /// [Optional] Executes operations only on repositories containing the file or directory,
+    /// e.g. `conf/layer.conf`
+    ///
+    /// Path is relative to the root of the working tree, or checked in HEAD of bare repositories
+    #[clap(long)]
+    pub contains_path: Option<PathBuf>,
     /// [Optional] Executes similar command to `git status --porcelain`
     ///
-    /// It will display only repositories modified in any way with `status --porcelain` result
+    /// It will display only repositories modified in any way with `status --porcelain --branch` result,
+    /// i.e. staged, unstaged, untracked and conflicted files together with current branch
+    /// and number of commits ahead and behind its upstream
     #[clap(long)]
     pub porcelain: bool,
-    /// [Optional] Founds repositories which have cherry-picks in git reflog and prints them
+    /// [Optional] Reports also files ignored by git in `--porcelain`
+    #[clap(long, requires = "porcelain")]
+    pub ignored: bool,
+    /// [Optional] Fetches remotes of all repositories, similar to `git fetch`
+    ///
+    /// Displays progress while fetching and repositories with updated references afterwards,
+    /// progress is displayed only when repositories are fetched one by one, i.e. without `--jobs`
+    #[clap(long)]
+    pub fetch: bool,
+    /// [Optional] Name of the remote fetched by `--fetch`, can be used multiple times, default: all remotes
+    #[clap(long, number_of_values = 1, requires = "fetch")]
+    pub remote: Vec<String>,
+    /// [Optional] Removes remote tracking branches which no longer exist on the remote in `--fetch`
+    #[clap(long, requires = "fetch")]
+    pub prune: bool,
+    /// [Optional] Tags downloaded by `--fetch`: auto, all or none, default: auto
+    ///
+    /// `auto` downloads tags pointing at fetched commits like `git fetch` does
+    #[clap(long, default_value = "auto", possible_values = &["auto", "all", "none"])]
+    pub tags: FetchTags,
+    /// [Optional] Fast-forwards current branches to their upstreams, similar to `git pull --ff-only`
+    ///
+    /// Upstreams are not fetched, combine with `--fetch` to fetch them first. Repositories with changes
+    /// in the working tree or diverged from upstream are not updated but reported
+    #[clap(long)]
+    pub update: bool,
+    /// [Optional] Rebases commits missing in upstream onto it in `--update`, similar to `git pull --rebase`
+    ///
+    /// Rebase is aborted if any commit doesn't apply cleanly
+    #[clap(long, requires = "update")]
+    pub rebase: bool,
+    /// [Optional] Displays repositories with current branch ahead or behind its upstream
+    ///
+    /// Only branches with configured upstream are compared
+    #[clap(long)]
+    pub sync_status: bool,
+    /// [Optional] Compares every local branch with its upstream in `--sync-status`
+    #[clap(long, requires = "sync-status")]
+    pub all_branches: bool,
+    /// [Optional] Displays submodules which aren't initialized, are checked out at a different commit
+    /// than recorded in the superproject or have changes
+    #[clap(long)]
+    pub submodule_status: bool,
+    /// [Optional] Founds repositories which have commits cherry-picked between current branch
+    /// and `--cherry-base` and prints them
+    ///
+    /// Commits are the same if one of them has `(cherry picked from commit ...)` trailer pointing at the other one
+    /// or if they introduce the same changes, like `git cherry` detects them
     #[clap(long)]
     pub find_cherry_picks: bool,
-    /// [Optional] Prints cherry-picks titles in repositories which have cherry-picks in git reflog
+    /// [Optional] Prints cherry-picks titles in repositories which have commits cherry-picked between current branch
+    /// and `--cherry-base`
     #[clap(long)]
     pub print_cherry_picks: bool,
-    /// [Optional] Prints repositories in which current user has commits in last `--number` of commits
-    ///
-    /// By default it looks for commits which belong to current git user
+    /// [Optional] Revision current branch is compared with when looking for cherry-picks, e.g. `origin/release`,
+    /// default: upstream of current branch
     #[clap(long)]
-    pub with_author: Option<Option<String>>,
-    /// [Optional] Used for options looking through git log, e.g. `--with-author`, `--show`.
-    /// Specifies number of last commits to look into, default: 10
+    pub cherry_base: Option<String>,
+    /// [Optional] Prints repositories in which given authors have commits in last `--number` of commits
+    ///
+    /// Every author is a regex matched against name or email of commit author and committer, identities
+    /// are resolved with `.mailmap`. By default it looks for commits of current git user, i.e. `user.name`
+    /// or `user.email`
+    #[clap(long, min_values = 0)]
+    pub with_author: Option<Vec<String>>,
+    /// [Optional] Used for options looking through git log, e.g. `--with-author`.
+    /// Specifies number of the newest matching commits to print, default: 10
     ///
     /// Ignored when either `--after` or `--before` is specified
     #[clap(short, long, default_value = "10")]
     pub number: u32,
+    /// [Optional] Used for options looking through git log, e.g. `--with-author`.
+    /// Looks only into commits newer than given date
+    ///
+    /// Accepts ISO dates, e.g. `2026-10-01` or `2026-10-01 12:00`, and relative ones, e.g. `2 weeks ago`, `yesterday`
+    #[clap(long)]
+    pub after: Option<String>,
+    /// [Optional] Used for options looking through git log, e.g. `--with-author`.
+    /// Looks only into commits older than given date
+    ///
+    /// Accepts ISO dates, e.g. `2026-10-15` or `2026-10-15 12:00`, and relative ones, e.g. `2 weeks ago`, `yesterday`
+    #[clap(long)]
+    pub before: Option<String>,
     /// [Optional] Executes custom git command on all repositories
-    #[clap(short, long)]
+    ///
+    /// Command is split into arguments like a shell would do it, so quotes and escapes can be used,
+    /// e.g. `-c "commit -m 'fix build'"`
+    #[clap(short, long, conflicts_with = "args")]
     pub cmd: Option<String>,
+    /// [Optional] Executes custom git command with arguments passed without any modifications,
+    /// e.g. `rgit exec -- log --oneline -5`
+    #[clap(last = true)]
+    pub args: Vec<String>,
+    /// [Optional] Executes command in user's shell on all repositories, e.g. `--sh "make clean"`
+    ///
+    /// Command is executed with the repository as working directory and can use environment variables
+    /// `RGIT_REPO_PATH`, `RGIT_REPO_NAME` and `RGIT_REPO_INDEX`
+    #[clap(long, conflicts_with_all = &["cmd", "args"])]
+    pub sh: Option<String>,
+    /// [Optional] Number of repositories processed in parallel, default: 1
+    ///
+    /// Output is still printed per repository in the same order as repositories are provided
+    #[clap(short, long, default_value = "1")]
+    pub jobs: usize,
+    /// [Optional] Continues with other repositories when operation fails on one of them
+    ///
+    /// Prints summary of failed repositories at the end and exits with non-zero code if there were any failures
+    #[clap(short, long)]
+    pub keep_going: bool,
+    /// [Optional] Format of the output: text, json or ndjson, default: text
+    ///
+    /// `json` prints a single array and `ndjson` one record per line, each record describes single repository and operation
+    /// with `path`, `operation`, `success`, `error` and operation specific fields, e.g. `exit_code`, `stdout` and `stderr`
+    /// of commands, `branch`, `upstream`, `ahead`, `behind` and `entries` of `--porcelain`, `remotes` of `--fetch`, `outcome` of `--update`, `branches` of `--sync-status`, `cherry_picks` or `commits`
+    #[clap(long, default_value = "text", possible_values = &["text", "json", "ndjson"])]
+    pub format: OutputFormat,
+}
+
+/// Compares two branches in every repository and prints commits missing on either of them
+///
+/// Commits with the same changes or cherry-picked with `-x` from a commit on the other branch
+/// are treated as present on both branches. Merge commits are not compared.
+///
+/// EXAMPLES:
+///
+/// rgit compare main release/1.0 -s
+///
+/// rgit scan | rgit compare origin/main origin/release/1.0 --format json
+#[derive(Clap, Debug)]
+#[clap(setting = AppSettings::ColoredHelp)]
+pub struct CompareOpts {
+    /// First compared branch or any other revision, e.g. `main`
+    pub base: String,
+    /// Second compared branch or any other revision, e.g. `release/1.0`
+    pub head: String,
+    /// [Optional] Reads repositories saved in the specified file (by `rgit scan`)
+    ///
+    /// Reads results from the file instead of stdin, default: ~/.rgit
+    #[clap(short, long)]
+    pub source_file: Option<Option<String>>,
+    /// [Optional] Number of repositories processed in parallel, default: 1
+    #[clap(short, long, default_value = "1")]
+    pub jobs: usize,
+    /// [Optional] Continues with other repositories when comparison fails on one of them
+    #[clap(short, long)]
+    pub keep_going: bool,
+    /// [Optional] Format of the output: text, json or ndjson, default: text
+    ///
+    /// Records contain `base`, `head`, `missing_branches`, `missing_in_base` and `missing_in_head`
+    #[clap(long, default_value = "text", possible_values = &["text", "json", "ndjson"])]
+    pub format: OutputFormat,
+}
+
+/// Prints commits of all repositories merged into a single timeline, newest first
+///
+/// Every commit is tagged with name of its repository.
+///
+/// EXAMPLES:
+///
+/// rgit log --after yesterday -s
+///
+/// rgit log --author "Jane|jane@example.com" --path conf/ --grep "^fix" -n 50 -s
+#[derive(Clap, Debug)]
+#[clap(setting = AppSettings::ColoredHelp)]
+pub struct LogOpts {
+    /// [Optional] Reads repositories saved in the specified file (by `rgit scan`)
+    ///
+    /// Reads results from the file instead of stdin, default: ~/.rgit
+    #[clap(short, long)]
+    pub source_file: Option<Option<String>>,
+    /// [Optional] Number of the newest commits to print, default: 10
+    ///
+    /// Ignored when either `--after` or `--before` is specified
+    #[clap(short, long, default_value = "10")]
+    pub number: u32,
+    /// [Optional] Prints only commits newer than given date
+    ///
+    /// Accepts ISO dates, e.g. `2026-10-01` or `2026-10-01 12:00`, and relative ones, e.g. `2 weeks ago`, `yesterday`
+    #[clap(long)]
+    pub after: Option<String>,
+    /// [Optional] Prints only commits older than given date
+    ///
+    /// Accepts ISO dates, e.g. `2026-10-15` or `2026-10-15 12:00`, and relative ones, e.g. `2 weeks ago`, `yesterday`
+    #[clap(long)]
+    pub before: Option<String>,
+    /// [Optional] Prints only commits with author or committer name or email matching the regex,
+    /// can be used multiple times
+    ///
+    /// Identities are resolved with `.mailmap` of the repository
+    #[clap(long, number_of_values = 1)]
+    pub author: Vec<String>,
+    /// [Optional] Prints only commits changing the path, can be used multiple times
+    #[clap(long, number_of_values = 1)]
+    pub path: Vec<String>,
+    /// [Optional] Prints only commits with message matching the regex
+    #[clap(long)]
+    pub grep: Option<Regex>,
+    /// [Optional] Number of repositories processed in parallel, default: 1
+    #[clap(short, long, default_value = "1")]
+    pub jobs: usize,
+    /// [Optional] Continues with other repositories when log can't be read from one of them
+    #[clap(short, long)]
+    pub keep_going: bool,
+    /// [Optional] Format of the output: text, json or ndjson, default: text
+    ///
+    /// Every record describes single commit with `repository`, `path`, `commit`, `summary`, `author`, `email`
+    /// and `time`
+    #[clap(long, default_value = "text", possible_values = &["text", "json", "ndjson"])]
+    pub format: OutputFormat,
+}
+
+/// Searches tracked files of all repositories and prints matching lines prefixed with paths of
+/// repositories, e.g. `meta-foo/conf/layer.conf:3:BBFILE_COLLECTIONS += "foo"`
+///
+/// EXAMPLES:
+///
+/// rgit grep PREFERRED_VERSION -j 8 -s
+///
+/// rgit grep -i "linux-yocto" --revision origin/master -s -- "*.bbappend" conf/
+#[derive(Clap, Debug)]
+#[clap(setting = AppSettings::ColoredHelp)]
+pub struct GrepOpts {
+    /// Regex searched in lines of tracked files
+    pub pattern: String,
+    /// [Optional] Ignores case of letters when matching the pattern
+    #[clap(short, long)]
+    pub ignore_case: bool,
+    /// [Optional] Searches files of given revision instead of the working tree, e.g. `origin/master`
+    #[clap(short, long)]
+    pub revision: Option<String>,
+    /// [Optional] Reads repositories saved in the specified file (by `rgit scan`)
+    ///
+    /// Reads results from the file instead of stdin, default: ~/.rgit
+    #[clap(short, long)]
+    pub source_file: Option<Option<String>>,
+    /// [Optional] Number of repositories processed in parallel, default: 1
+    #[clap(short, long, default_value = "1")]
+    pub jobs: usize,
+    /// [Optional] Continues with other repositories when search fails on one of them
+    #[clap(short, long)]
+    pub keep_going: bool,
+    /// [Optional] Format of the output: text, json or ndjson, default: text
+    ///
+    /// Records contain `matches`, a list of matching lines with `path`, `line` and `text`
+    #[clap(long, default_value = "text", possible_values = &["text", "json", "ndjson"])]
+    pub format: OutputFormat,
+    /// [Optional] Searches only files matching any of the pathspecs, e.g. `conf/` or `*.bb`
+    #[clap(last = true)]
+    pub paths: Vec<String>,
+}
+
+/// Lists repositories saved by `rgit scan -s`
+///
+/// EXAMPLES:
+///
+/// rgit list
+///
+/// rgit list --prune
+#[derive(Clap, Debug)]
+#[clap(setting = AppSettings::ColoredHelp)]
+pub struct ListOpts {
+    /// [Optional] File with scan results, default: ~/.rgit
+    #[clap(short, long)]
+    pub source_file: Option<String>,
+    /// [Optional] Removes repositories which paths no longer hold a git repository from the file
+    ///
+    /// Removed paths are printed to stderr
+    #[clap(long)]
+    pub prune: bool,
+    /// [Optional] Format of printed repositories: text, json or ndjson, default: text
+    ///
+    /// Records of json formats contain all saved metadata, e.g. `kind`, `remote`, `branch`, `groups` and `tags`
+    #[clap(long, default_value = "text", possible_values = &["text", "json", "ndjson"])]
+    pub format: OutputFormat,
+}
+
+/// Manages named groups of repositories saved by `rgit scan -s`
+///
+/// Groups are stored in the scan file and kept when the directory is scanned again.
+/// Repositories are given by their names or paths as saved in the file.
+///
+/// EXAMPLES:
+///
+/// rgit group add bsp-layers meta-foo meta-bar
+///
+/// rgit group remove bsp-layers meta-bar
+///
+/// rgit group list
+///
+/// rgit exec -s --group bsp-layers --porcelain
+#[derive(Clap, Debug)]
+#[clap(setting = AppSettings::ColoredHelp)]
+pub struct GroupOpts {
+    /// [Optional] File with scan results, default: ~/.rgit
+    #[clap(short, long)]
+    pub source_file: Option<String>,
+    #[clap(subcommand)]
+    pub action: LabelAction,
+}
+
+/// Manages tags of repositories saved by `rgit scan -s`
+///
+/// Tags are stored in the scan file and kept when the directory is scanned again.
+/// Repositories are given by their names or paths as saved in the file.
+///
+/// EXAMPLES:
+///
+/// rgit tag add vendor meta-foo
+///
+/// rgit tag list vendor
+///
+/// rgit exec -s --tag '!vendor' --fetch
+#[derive(Clap, Debug)]
+#[clap(setting = AppSettings::ColoredHelp)]
+pub struct TagOpts {
+    /// [Optional] File with scan results, default: ~/.rgit
+    #[clap(short, long)]
+    pub source_file: Option<String>,
+    #[clap(subcommand)]
+    pub action: LabelAction,
+}
+
+/// Changes or lists groups and tags
+#[derive(Clap, Debug)]
+pub enum LabelAction {
+    /// Adds repositories to the group or tag
+    Add {
+        /// Name of the group or tag
+        name: String,
+        /// Names or paths of repositories
+        #[clap(required = true)]
+        repos: Vec<String>,
+    },
+    /// Removes repositories from the group or tag, all of them if no repository is given
+    Remove {
+        /// Name of the group or tag
+        name: String,
+        /// Names or paths of repositories
+        repos: Vec<String>,
+    },
+    /// Lists groups or tags with their repositories
+    List {
+        /// Lists only repositories of the given group or tag
+        name: Option<String>,
+    },
 }
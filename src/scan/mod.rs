@@ -1 +1,3 @@
+#[allow(clippy::module_inception)]
 pub mod scan;
+pub mod scan_file;
@@ -1,12 +1,17 @@
 use anyhow::{Context, Result};
 use git2::Repository;
-use log::{debug, trace};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::Match;
+use log::{debug, trace, warn};
 use walkdir::WalkDir;
 
-use std::fs::File;
-use std::io::{prelude::*, LineWriter};
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
+use std::str::FromStr;
 
+use super::scan_file::{ScanEntry, ScanFile, ScanRoot, ScanSettings};
+use crate::exec::presenter::OutputFormat;
+use crate::exec::repo::{current_branch, repo_kind};
 use crate::options::opts::ScanOpts;
 
 /// Starts `scan` command
@@ -17,110 +22,525 @@ use crate::options::opts::ScanOpts;
 pub fn run(opts: &ScanOpts) -> Result<()> {
     debug!("ENTER scan run: {:?}", opts);
 
-    let repos = get_repo_paths(
-        std::env::current_dir()
-            .context("Failed to get current directory path")?
-            .as_path(),
-        opts.min_depth,
-        opts.max_depth,
-        opts.relative,
-    )
-    .context("Failed to scan paths")?;
+    if opts.refresh {
+        let path = get_scan_file_path(&opts.save_to_file)?;
+        refresh_file(&path).context(format!("Failed to refresh {}", path.display()))?;
+
+        trace!("EXIT scan run");
+        return Ok(());
+    }
+
+    let file = scan_roots(&get_scan_roots(opts)?).context("Failed to scan paths")?;
 
     match &opts.save_to_file {
-        None => print_paths_to_stdout(repos),
-        Some(save_to_file) => print_paths_to_file(
-            repos,
-            match save_to_file {
-                None => crate::tools::get_default_scan_path()
-                    .context("Failed to get default scan path")?,
-                Some(path) => PathBuf::from(path),
-            },
-        )
-        .context("Failed to print paths to file")?,
+        None => print_paths_to_stdout(file.repositories, opts.format)?,
+        Some(_) => print_paths_to_file(file, get_scan_file_path(&opts.save_to_file)?, opts.append)
+            .context("Failed to print paths to file")?,
     };
 
     trace!("EXIT scan run");
     Ok(())
 }
 
-fn print_paths_to_stdout(repos: Vec<PathBuf>) {
-    for repo in repos {
-        println!("{}", repo.display());
+/// Directory given to `rgit scan` with optional depth limits, e.g. `~/src:2` or `/opt/bsp:1..4`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScanPath {
+    /// Scanned directory
+    pub path: PathBuf,
+    /// Level of directories on which scan starts, `--min-depth` if not given
+    pub min_depth: Option<usize>,
+    /// Level of directories on which scan stops, `--max-depth` if not given
+    pub max_depth: Option<usize>,
+}
+
+impl FromStr for ScanPath {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let depths = s.rsplit_once(':').and_then(|(path, depths)| {
+            let (min, max) = match depths.split_once("..") {
+                Some((min, max)) => (Some(min.parse().ok()?), max),
+                None => (None, depths),
+            };
+            Some((path, min, max.parse().ok()?))
+        });
+
+        // paths containing `:` not followed by depths are taken as they are
+        Ok(match depths {
+            Some((path, min_depth, max_depth)) => ScanPath {
+                path: PathBuf::from(path),
+                min_depth,
+                max_depth: Some(max_depth),
+            },
+            None => ScanPath {
+                path: PathBuf::from(s),
+                min_depth: None,
+                max_depth: None,
+            },
+        })
+    }
+}
+
+/// Creates scanned directories with their settings from command line options
+///
+/// Current directory is scanned if no directory is given. Paths of directories and of `--base`
+/// are made absolute, so the scan can be refreshed from any directory.
+fn get_scan_roots(opts: &ScanOpts) -> Result<Vec<ScanRoot>> {
+    let current_dir = std::env::current_dir().context("Failed to get current directory path")?;
+
+    let absolute = |path: &Path| {
+        current_dir
+            .join(path)
+            .canonicalize()
+            .context(format!("Failed to find directory {}", path.display()))
+    };
+
+    let base = match (&opts.base, opts.relative) {
+        (Some(base), _) => Some(absolute(base)?),
+        (None, true) => Some(current_dir.clone()),
+        (None, false) => None,
+    };
+
+    let paths = match opts.roots.is_empty() {
+        true => vec![ScanPath {
+            path: current_dir.clone(),
+            min_depth: None,
+            max_depth: None,
+        }],
+        false => opts.roots.clone(),
+    };
+
+    paths
+        .iter()
+        .map(|root| {
+            Ok(ScanRoot {
+                path: absolute(&root.path)?,
+                settings: ScanSettings {
+                    min_depth: root.min_depth.unwrap_or(opts.min_depth),
+                    max_depth: root.max_depth.unwrap_or(opts.max_depth),
+                    relative: opts.relative,
+                    base: base.clone(),
+                    nested: opts.nested,
+                    submodules: opts.submodules,
+                    worktrees: opts.worktrees,
+                    excludes: opts.exclude.clone(),
+                },
+            })
+        })
+        .collect()
+}
+
+/// Scans all directories and merges their results, repositories found in more of them are listed once
+///
+/// # Arguments
+///
+/// * `roots` - scanned directories with settings of their scans
+fn scan_roots(roots: &[ScanRoot]) -> Result<ScanFile> {
+    let mut file: Option<ScanFile> = None;
+
+    for root in roots {
+        debug!("Scanning {}", root.path.display());
+
+        let repos = get_repo_paths(&root.path, &root.settings)
+            .context(format!("Failed to scan {}", root.path.display()))?;
+        let scan = ScanFile::new(root.clone(), repos);
+
+        file = Some(match file {
+            Some(mut file) => {
+                file.merge(scan);
+                file
+            }
+            None => scan,
+        });
+    }
+
+    file.context("No directories to scan")
+}
+
+/// Returns path to the scan file given with `--save-to-file`, default file if it's given without path
+fn get_scan_file_path(save_to_file: &Option<Option<String>>) -> Result<PathBuf> {
+    match save_to_file.as_ref().and_then(|path| path.as_ref()) {
+        Some(path) => Ok(PathBuf::from(path)),
+        None => crate::tools::get_default_scan_path().context("Failed to get default scan path"),
     }
 }
 
-fn print_paths_to_file(repos: Vec<PathBuf>, path: PathBuf) -> Result<()> {
+/// Prints paths of repositories, or all their metadata in json formats
+///
+/// # Arguments
+///
+/// * `repos` - printed repositories
+/// * `format` - format of the output
+pub fn print_paths_to_stdout(repos: Vec<ScanEntry>, format: OutputFormat) -> Result<()> {
+    match format {
+        OutputFormat::Text => {
+            for repo in repos {
+                println!("{}", repo.path.display());
+            }
+        }
+        OutputFormat::Json => println!(
+            "{}",
+            serde_json::to_string_pretty(&repos).context("Failed to serialize repositories")?
+        ),
+        OutputFormat::Ndjson => {
+            for repo in repos {
+                println!(
+                    "{}",
+                    serde_json::to_string(&repo).context("Failed to serialize repository")?
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Saves scan results to the file
+///
+/// # Arguments
+///
+/// * `file` - scan results
+/// * `path` - path to the file
+/// * `append` - results are merged with repositories already saved in the file instead of
+///   replacing them
+fn print_paths_to_file(mut file: ScanFile, path: PathBuf, append: bool) -> Result<()> {
     debug!("Printing results to file {}", path.display());
 
-    let f = File::create(&path).context(format!("Failed to create file {}", path.display()))?;
-    let mut f = LineWriter::new(f);
+    if path.exists() {
+        match ScanFile::read(&path) {
+            Ok(mut previous) if append => {
+                previous.merge(file);
+                file = previous;
+            }
+            Ok(previous) => file.keep_labels(&previous),
+            Err(e) if append => return Err(e).context("Failed to read results to append to"),
+            Err(e) => warn!("Groups and tags of previous scan are lost: {:#}", e),
+        }
+    }
+
+    file.write(&path)
+}
+
+/// Scans again all directories recorded in the file with their settings
+///
+/// Repositories which are no longer found are removed, groups and tags of the others are kept.
+///
+/// # Arguments
+///
+/// * `path` - path to the file
+fn refresh_file(path: &Path) -> Result<()> {
+    let previous = ScanFile::read(path)?;
+
+    if previous.roots.is_empty() {
+        anyhow::bail!("No scanned directories recorded, scan them with -s first");
+    }
+
+    let mut file = scan_roots(&previous.roots)?;
+    file.keep_labels(&previous);
+
+    file.write(path)
+}
+
+/// Creates scan entry describing the repository found at `path`
+///
+/// # Arguments
+///
+/// * `repository` - found repository
+/// * `path` - path to the repository as printed by scan
+/// * `parent` - path to the superproject if the repository is its submodule
+fn scan_entry(repository: &Repository, path: &Path, parent: Option<&Path>) -> ScanEntry {
+    ScanEntry {
+        parent: parent.map(PathBuf::from),
+        kind: repo_kind(repository),
+        remote: default_remote_url(repository),
+        branch: current_branch(repository).unwrap_or_default(),
+        ..ScanEntry::new(path)
+    }
+}
+
+/// Returns URL of `origin`, or the first configured remote if there is no `origin`
+fn default_remote_url(repository: &Repository) -> Option<String> {
+    let remotes = repository.remotes().ok()?;
+    let name = match remotes.iter().flatten().any(|name| name == "origin") {
+        true => "origin",
+        false => remotes.iter().flatten().next()?,
+    };
+
+    repository.find_remote(name).ok()?.url().map(String::from)
+}
+
+/// Name of files with rules excluding directories from scan, in gitignore syntax
+const IGNORE_FILE_NAME: &str = ".rgitignore";
 
-    for repo in repos {
-        f.write_all(
-            repo.to_str()
-                .context("Error while converting path to str")?
-                .as_bytes(),
-        )
-        .context("Error while writing to file")?;
-        f.write_all(b"\n").context("Error while writing to file")?;
+/// Decides which directories are skipped by scan
+///
+/// Directories are excluded by `--exclude` globs and `.rgitignore` files at any level of scanned tree.
+/// Rules of `.rgitignore` in deeper directories take precedence, like in `.gitignore`.
+struct ScanFilter {
+    root: PathBuf,
+    excludes: Gitignore,
+    ignore_files: HashMap<PathBuf, Option<Gitignore>>,
+}
+
+impl ScanFilter {
+    /// Creates filter of directories under `root`
+    ///
+    /// # Arguments
+    ///
+    /// * `root` - root of the scan, excludes are relative to it
+    /// * `excludes` - globs in gitignore syntax, e.g. `node_modules`, `build/tmp`
+    fn new(root: &Path, excludes: &[String]) -> Result<ScanFilter> {
+        let mut builder = GitignoreBuilder::new(root);
+
+        for exclude in excludes {
+            builder
+                .add_line(None, exclude)
+                .context(format!("Invalid exclude: {}", exclude))?;
+        }
+
+        Ok(ScanFilter {
+            root: PathBuf::from(root),
+            excludes: builder.build().context("Failed to build excludes")?,
+            ignore_files: HashMap::new(),
+        })
     }
 
-    Ok(f.flush().context("Failed to flush file")?)
+    /// Returns true if the directory and everything below it should be skipped
+    fn is_excluded(&mut self, dir: &Path) -> bool {
+        if dir == self.root {
+            return false;
+        }
+
+        if self.excludes.matched(dir, true).is_ignore() {
+            return true;
+        }
+
+        let parents: Vec<PathBuf> = dir
+            .ancestors()
+            .skip(1)
+            .take_while(|parent| parent.starts_with(&self.root))
+            .map(PathBuf::from)
+            .collect();
+
+        for parent in parents {
+            if let Some(ignore_file) = self.ignore_file(parent) {
+                match ignore_file.matched(dir, true) {
+                    Match::Ignore(_) => return true,
+                    Match::Whitelist(_) => return false,
+                    Match::None => continue,
+                }
+            }
+        }
+
+        false
+    }
+
+    /// Returns rules of `.rgitignore` in the directory, `None` if there is no such file
+    fn ignore_file(&mut self, dir: PathBuf) -> Option<&Gitignore> {
+        self.ignore_files
+            .entry(dir)
+            .or_insert_with_key(|dir| {
+                let path = dir.join(IGNORE_FILE_NAME);
+
+                if !path.is_file() {
+                    return None;
+                }
+
+                let (ignore_file, err) = Gitignore::new(&path);
+                if let Some(err) = err {
+                    warn!("Invalid rules in {}: {}", path.display(), err);
+                }
+
+                Some(ignore_file)
+            })
+            .as_ref()
+    }
 }
 
-fn get_repo_paths(
-    root: &Path,
-    min_depth: usize,
-    max_depth: usize,
-    relative: bool,
-) -> Result<Vec<PathBuf>> {
+fn get_repo_paths(root: &Path, settings: &ScanSettings) -> Result<Vec<ScanEntry>> {
     trace!("ENTER get_repo_paths, root={:?}", root);
 
     let mut res = Vec::new();
+    let mut found = HashSet::new();
+    let mut filter = ScanFilter::new(root, &settings.excludes)?;
+    let relative_to = settings.relative_to(root);
 
-    for entry in WalkDir::new(root).min_depth(min_depth).max_depth(max_depth) {
+    let mut walker = WalkDir::new(root)
+        .min_depth(settings.min_depth)
+        .max_depth(settings.max_depth)
+        .into_iter()
+        .filter_entry(|entry| {
+            !entry.file_type().is_dir()
+                || (entry.file_name() != ".git" && !filter.is_excluded(entry.path()))
+        });
+
+    while let Some(entry) = walker.next() {
         let entry = entry.context("Failed to find entry")?;
-        let path = entry.path();
 
-        if path.ends_with(".git") {
+        // only directories or links to them can be repositories
+        if entry.file_type().is_file() {
             continue;
         }
 
-        let path = match relative {
-            true => path
-                .strip_prefix(std::env::current_dir().context("Failed to get current dir")?)
-                .context("Failed to create relative path")?,
-            false => path,
+        // repositories outside of the base keep absolute paths
+        let path = match relative_to {
+            Some(base) => entry
+                .path()
+                .strip_prefix(base)
+                .unwrap_or_else(|_| entry.path()),
+            None => entry.path(),
         };
 
-        match Repository::open(path) {
-            Ok(_) => {
+        // opening a directory with libgit2 is costly, so only likely repositories are opened
+        if !looks_like_repository(entry.path()) {
+            continue;
+        }
+
+        match Repository::open(entry.path()) {
+            Ok(repository) => {
                 trace!("Found repository in {:?}", path);
-                res.push(PathBuf::from(
-                    path.to_str().context("Failed to convert path to str")?,
-                ));
+
+                let path = PathBuf::from(path.to_str().context("Failed to convert path to str")?);
+
+                // submodules found by walking nested repositories are already listed
+                if found.insert(path.clone()) {
+                    res.push(scan_entry(&repository, &path, None));
+                }
+
+                if settings.submodules {
+                    add_submodules(&repository, &path, &mut res, &mut found)?;
+                }
+                if settings.worktrees {
+                    add_worktrees(&repository, relative_to, &mut res, &mut found)?;
+                }
+
+                // repositories nested in the found one, e.g. build dirs or vendored clones,
+                // are looked for only on demand
+                if !settings.nested && entry.file_type().is_dir() {
+                    walker.skip_current_dir();
+                }
             }
             Err(_) => continue,
         }
     }
 
     debug!(
-        "Found {} paths in root={:?}, min_depth={}, max_depth={}",
+        "Found {} paths in root={:?}, settings={:?}",
         res.len(),
         root,
-        min_depth,
-        max_depth
+        settings
     );
 
     Ok(res)
 }
 
+/// Checks if the directory has a `.git` entry or the layout of a bare repository
+///
+/// # Arguments
+///
+/// * `path` - checked directory
+fn looks_like_repository(path: &Path) -> bool {
+    path.join(".git").exists() || (path.join("HEAD").is_file() && path.join("objects").is_dir())
+}
+
+/// Adds initialized submodules of the repository, and their submodules, to scan results
+///
+/// # Arguments
+///
+/// * `repository` - superproject
+/// * `path` - path to the superproject as printed by scan
+/// * `res` - scan results
+/// * `found` - paths of repositories already in scan results, they are not added again
+fn add_submodules(
+    repository: &Repository,
+    path: &Path,
+    res: &mut Vec<ScanEntry>,
+    found: &mut HashSet<PathBuf>,
+) -> Result<()> {
+    let submodules = repository
+        .submodules()
+        .context(format!("Failed to list submodules of {}", path.display()))?;
+
+    for submodule in submodules {
+        // submodules which aren't cloned can't be operated on
+        let submodule_repository = match submodule.open() {
+            Ok(submodule_repository) => submodule_repository,
+            Err(_) => continue,
+        };
+        let submodule_path = path.join(submodule.path());
+
+        trace!("Found submodule in {:?}", submodule_path);
+
+        if found.insert(submodule_path.clone()) {
+            res.push(scan_entry(
+                &submodule_repository,
+                &submodule_path,
+                Some(path),
+            ));
+        }
+
+        add_submodules(&submodule_repository, &submodule_path, res, found)?;
+    }
+
+    Ok(())
+}
+
+/// Adds valid linked worktrees of the repository to scan results
+///
+/// # Arguments
+///
+/// * `repository` - repository which worktrees are listed
+/// * `relative_to` - worktrees under this directory are added with paths relative to it
+/// * `res` - scan results
+/// * `found` - paths of repositories already in scan results, they are not added again
+fn add_worktrees(
+    repository: &Repository,
+    relative_to: Option<&Path>,
+    res: &mut Vec<ScanEntry>,
+    found: &mut HashSet<PathBuf>,
+) -> Result<()> {
+    for name in repository
+        .worktrees()
+        .context("Failed to list worktrees")?
+        .iter()
+        .flatten()
+    {
+        let worktree = repository
+            .find_worktree(name)
+            .context(format!("Failed to find worktree {}", name))?;
+
+        // worktrees removed without `git worktree prune` are still listed
+        if worktree.validate().is_err() {
+            continue;
+        }
+
+        let path = match relative_to {
+            Some(base) => worktree
+                .path()
+                .strip_prefix(base)
+                .unwrap_or_else(|_| worktree.path()),
+            None => worktree.path(),
+        };
+
+        trace!("Found worktree in {:?}", path);
+
+        if found.insert(PathBuf::from(path)) {
+            let worktree_repository = Repository::open_from_worktree(&worktree)
+                .context(format!("Failed to open worktree {}", name))?;
+
+            res.push(scan_entry(&worktree_repository, path, None));
+        }
+    }
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::exec::report::RepoKind;
+    use crate::scan::scan_file::Label;
     use git2::Repository;
-    use std::io::BufReader;
     use tempfile::tempfile_in;
     use tempfile::NamedTempFile;
     use tempfile::TempDir;
@@ -131,7 +551,13 @@ mod tests {
         let repo_dir = TempDir::new_in(&root)?;
         let _ = Repository::init(repo_dir.path())?;
 
-        let res = get_repo_paths(root.path(), 0, 3, false)?;
+        let res = get_repo_paths(
+            root.path(),
+            &ScanSettings {
+                max_depth: 3,
+                ..Default::default()
+            },
+        )?;
 
         println!("{:?}", res);
 
@@ -161,7 +587,13 @@ mod tests {
         let dir_lvl_4_1 = TempDir::new_in(&dir_lvl_3_1)?;
         let _ = Repository::init(dir_lvl_4_1.path())?;
 
-        let res = get_repo_paths(root.path(), 0, 3, false)?;
+        let res = get_repo_paths(
+            root.path(),
+            &ScanSettings {
+                max_depth: 3,
+                ..Default::default()
+            },
+        )?;
 
         println!("{:?}", res);
 
@@ -191,7 +623,13 @@ mod tests {
         let _ = tempfile_in(&dir_2)?;
         let _ = tempfile_in(&dir_2)?;
 
-        let res = get_repo_paths(root.path(), 0, 3, false)?;
+        let res = get_repo_paths(
+            root.path(),
+            &ScanSettings {
+                max_depth: 3,
+                ..Default::default()
+            },
+        )?;
 
         println!("{:?}", res);
 
@@ -200,28 +638,461 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn get_repo_paths_nested_repos() -> Result<()> {
+        let root = TempDir::new()?;
+
+        for dir in ["poky", "poky/build/downloads/git2/foo", "meta-bar"] {
+            let _ = Repository::init(root.path().join(dir))?;
+        }
+
+        let settings = ScanSettings {
+            max_depth: 5,
+            ..Default::default()
+        };
+        assert_eq!(
+            sorted_paths(get_repo_paths(root.path(), &settings)?),
+            vec![root.path().join("meta-bar"), root.path().join("poky")]
+        );
+
+        let settings = ScanSettings {
+            nested: true,
+            ..settings
+        };
+        assert_eq!(
+            sorted_paths(get_repo_paths(root.path(), &settings)?),
+            vec![
+                root.path().join("meta-bar"),
+                root.path().join("poky"),
+                root.path().join("poky/build/downloads/git2/foo"),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn get_repo_paths_submodules() -> Result<()> {
+        let upstream_dir = TempDir::new()?;
+        let upstream = Repository::init(upstream_dir.path())?;
+        let signature = git2::Signature::now("Jane Doe", "jane@example.com")?;
+        let tree = upstream.find_tree(upstream.index()?.write_tree()?)?;
+        upstream.commit(Some("HEAD"), &signature, &signature, "Init", &tree, &[])?;
+
+        let root = TempDir::new()?;
+        let product = Repository::init(root.path().join("product"))?;
+        let url = upstream_dir.path().to_str().unwrap();
+        for name in ["poky", "meta-foo"] {
+            let mut submodule = product.submodule(url, Path::new(name), true)?;
+            submodule.clone(None)?;
+            submodule.add_finalize()?;
+        }
+        std::fs::remove_file(root.path().join("product/meta-foo/.git"))?;
+
+        let settings = ScanSettings {
+            max_depth: 5,
+            ..Default::default()
+        };
+        assert_eq!(get_repo_paths(root.path(), &settings)?.len(), 1);
+
+        let settings = ScanSettings {
+            submodules: true,
+            ..settings
+        };
+        let product_path = root.path().join("product");
+        let res: Vec<(PathBuf, Option<PathBuf>)> = get_repo_paths(root.path(), &settings)?
+            .into_iter()
+            .map(|entry| (entry.path, entry.parent))
+            .collect();
+        assert_eq!(
+            res,
+            vec![
+                (product_path.clone(), None),
+                (product_path.join("poky"), Some(product_path.clone())),
+            ]
+        );
+
+        let settings = ScanSettings {
+            nested: true,
+            ..settings
+        };
+        assert_eq!(get_repo_paths(root.path(), &settings)?.len(), 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn get_repo_paths_worktrees_and_bare_repos() -> Result<()> {
+        let root = TempDir::new()?;
+        let outside = TempDir::new()?;
+
+        let repository = Repository::init(root.path().join("meta-foo"))?;
+        let signature = git2::Signature::now("Jane Doe", "jane@example.com")?;
+        let tree = repository.find_tree(repository.index()?.write_tree()?)?;
+        repository.commit(Some("HEAD"), &signature, &signature, "Init", &tree, &[])?;
+        let worktree_path = outside.path().join("meta-foo-release");
+        repository.worktree("release", &worktree_path, None)?;
+        let _ = Repository::init_bare(root.path().join("mirror.git"))?;
+
+        let settings = ScanSettings {
+            max_depth: 3,
+            ..Default::default()
+        };
+        let mut kinds: Vec<(PathBuf, RepoKind)> = get_repo_paths(root.path(), &settings)?
+            .into_iter()
+            .map(|entry| (entry.path, entry.kind))
+            .collect();
+        kinds.sort_by(|first, second| first.0.cmp(&second.0));
+        assert_eq!(
+            kinds,
+            vec![
+                (root.path().join("meta-foo"), RepoKind::Normal),
+                (root.path().join("mirror.git"), RepoKind::Bare),
+            ]
+        );
+
+        let settings = ScanSettings {
+            worktrees: true,
+            ..settings
+        };
+        let res = get_repo_paths(root.path(), &settings)?;
+        assert_eq!(res.len(), 3);
+
+        let worktree = res
+            .iter()
+            .find(|entry| entry.kind == RepoKind::Worktree)
+            .unwrap();
+        assert_eq!(worktree.path, worktree_path.canonicalize()?);
+        assert_eq!(worktree.name, "meta-foo-release");
+        assert_eq!(worktree.branch, Some(String::from("release")));
+
+        Ok(())
+    }
+
+    #[test]
+    fn get_repo_paths_collects_metadata() -> Result<()> {
+        let root = TempDir::new()?;
+
+        let repository = Repository::init(root.path().join("meta-foo"))?;
+        repository.remote("backup", "https://example.com/backup/meta-foo.git")?;
+        repository.remote("origin", "https://example.com/meta-foo.git")?;
+        repository.set_head("refs/heads/kirkstone")?;
+
+        let settings = ScanSettings {
+            max_depth: 3,
+            ..Default::default()
+        };
+        let res = get_repo_paths(root.path(), &settings)?;
+
+        assert_eq!(res.len(), 1);
+        assert_eq!(res[0].name, "meta-foo");
+        assert_eq!(
+            res[0].remote,
+            Some(String::from("https://example.com/meta-foo.git"))
+        );
+        assert_eq!(res[0].branch, Some(String::from("kirkstone")));
+        assert!(res[0].tags.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn get_repo_paths_skips_excluded_dirs() -> Result<()> {
+        let root = TempDir::new()?;
+
+        for dir in [
+            "meta-foo",
+            "build/tmp/work/foo",
+            "node_modules/bar",
+            "sources/vendor/baz",
+            "sources/vendor/kept",
+            "sources/meta-bar",
+        ] {
+            let _ = Repository::init(root.path().join(dir))?;
+        }
+
+        std::fs::write(root.path().join(".rgitignore"), "node_modules/\n")?;
+        std::fs::write(
+            root.path().join("sources/.rgitignore"),
+            "vendor/*\n!vendor/kept\n",
+        )?;
+
+        let mut settings = ScanSettings {
+            max_depth: 5,
+            excludes: vec![String::from("build")],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            sorted_paths(get_repo_paths(root.path(), &settings)?),
+            vec![
+                root.path().join("meta-foo"),
+                root.path().join("sources/meta-bar"),
+                root.path().join("sources/vendor/kept"),
+            ]
+        );
+
+        settings.excludes = vec![String::from("{")];
+        assert!(get_repo_paths(root.path(), &settings).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn looks_like_repository_test() -> Result<()> {
+        let root = TempDir::new()?;
+        let _ = Repository::init(root.path().join("normal"))?;
+        let _ = Repository::init_bare(root.path().join("bare.git"))?;
+        std::fs::create_dir_all(root.path().join("plain/objects"))?;
+
+        assert!(looks_like_repository(&root.path().join("normal")));
+        assert!(looks_like_repository(&root.path().join("bare.git")));
+        assert!(!looks_like_repository(&root.path().join("plain")));
+        assert!(!looks_like_repository(root.path()));
+
+        Ok(())
+    }
+
+    fn sorted_paths(entries: Vec<ScanEntry>) -> Vec<PathBuf> {
+        let mut paths: Vec<PathBuf> = entries.into_iter().map(|entry| entry.path).collect();
+        paths.sort();
+        paths
+    }
+
     #[test]
     fn print_paths_to_file_ok() -> Result<()> {
         let temp = NamedTempFile::new()?;
+        let vec = ["/some", "/random", "/vector"]
+            .iter()
+            .map(|path| ScanEntry::new(Path::new(path)))
+            .collect();
+
+        let root = ScanRoot::new(Path::new("/"));
+        print_paths_to_file(
+            ScanFile::new(root.clone(), vec),
+            PathBuf::from(temp.path()),
+            false,
+        )?;
+
+        let file = ScanFile::read(temp.path())?;
+
+        assert_eq!(file.roots, vec![root.clone()]);
+        assert_eq!(file.repositories[0].path, PathBuf::from("/some"));
+        assert_eq!(file.repositories[1].path, PathBuf::from("/random"));
+        assert_eq!(file.repositories[2].path, PathBuf::from("/vector"));
+
+        let mut labeled = file.clone();
+        labeled.repositories[1].groups = vec![String::from("bsp")];
+        labeled.write(temp.path())?;
+
+        let vec = vec![ScanEntry::new(Path::new("/random"))];
+        print_paths_to_file(ScanFile::new(root, vec), PathBuf::from(temp.path()), false)?;
+
+        let file = ScanFile::read(temp.path())?;
+        assert_eq!(file.repositories.len(), 1);
+        assert_eq!(file.repositories[0].groups, vec!["bsp"]);
+
         let vec = vec![
-            PathBuf::from("/some"),
-            PathBuf::from("/random"),
-            PathBuf::from("/vector"),
+            ScanEntry::new(Path::new("/other/tools")),
+            ScanEntry::new(Path::new("/random")),
         ];
+        print_paths_to_file(
+            ScanFile::new(ScanRoot::new(Path::new("/other")), vec),
+            PathBuf::from(temp.path()),
+            true,
+        )?;
+
+        let file = ScanFile::read(temp.path())?;
+        assert_eq!(file.roots.len(), 2);
+        assert_eq!(
+            sorted_paths(file.repositories.clone()),
+            vec![PathBuf::from("/other/tools"), PathBuf::from("/random")]
+        );
+        assert_eq!(file.repositories[0].groups, vec!["bsp"]);
 
-        print_paths_to_file(vec, PathBuf::from(temp.path()))?;
+        Ok(())
+    }
 
-        let reader = BufReader::new(temp);
+    #[test]
+    fn print_paths_to_file_appends_same_relative_paths_of_other_bases() -> Result<()> {
+        let root_a = TempDir::new()?;
+        let root_b = TempDir::new()?;
+        let _ = Repository::init(root_a.path().join("docs"))?;
+        let _ = Repository::init(root_a.path().join("x"))?;
+        let _ = Repository::init(root_b.path().join("docs"))?;
 
-        let mut res = Vec::new();
+        let temp = NamedTempFile::new()?;
+        for (root, append) in &[(&root_a, false), (&root_b, true)] {
+            let mut root = ScanRoot::new(root.path());
+            root.settings.relative = true;
+            let repos = get_repo_paths(&root.path, &root.settings)?;
 
-        for line in reader.lines() {
-            res.push(line?);
+            print_paths_to_file(
+                ScanFile::new(root, repos),
+                PathBuf::from(temp.path()),
+                *append,
+            )?;
         }
 
-        assert_eq!(res[0], "/some");
-        assert_eq!(res[1], "/random");
-        assert_eq!(res[2], "/vector");
+        let file = ScanFile::read(temp.path())?;
+        let mut resolved: Vec<PathBuf> = file
+            .repositories
+            .iter()
+            .map(|entry| file.resolve(entry))
+            .collect();
+        resolved.sort();
+
+        let mut expected = vec![
+            root_a.path().join("docs"),
+            root_a.path().join("x"),
+            root_b.path().join("docs"),
+        ];
+        expected.sort();
+
+        assert_eq!(resolved, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn refresh_file_rescans_recorded_roots() -> Result<()> {
+        let root_a = TempDir::new()?;
+        let root_b = TempDir::new()?;
+        let _ = Repository::init(root_a.path().join("meta-foo"))?;
+        let _ = Repository::init(root_a.path().join("meta-bar"))?;
+        let _ = Repository::init(root_b.path().join("tools"))?;
+
+        let temp = NamedTempFile::new()?;
+        let settings = ScanSettings {
+            relative: true,
+            ..Default::default()
+        };
+
+        let mut file = ScanFile::new(
+            ScanRoot::new(root_a.path()),
+            get_repo_paths(root_a.path(), &ScanSettings::default())?,
+        );
+        let root_b = ScanRoot {
+            path: PathBuf::from(root_b.path()),
+            settings,
+        };
+        file.merge(ScanFile::new(
+            root_b.clone(),
+            get_repo_paths(&root_b.path, &root_b.settings)?,
+        ));
+        file.add_label(Label::Tag, "vendor", &[String::from("meta-foo")])?;
+        file.write(temp.path())?;
+
+        std::fs::remove_dir_all(root_a.path().join("meta-bar"))?;
+        let _ = Repository::init(root_b.path.join("poky"))?;
+
+        refresh_file(temp.path())?;
+
+        let file = ScanFile::read(temp.path())?;
+        assert_eq!(file.roots.len(), 2);
+        assert_eq!(
+            sorted_paths(file.repositories.clone()),
+            vec![
+                root_a.path().join("meta-foo"),
+                PathBuf::from("poky"),
+                PathBuf::from("tools"),
+            ]
+        );
+        assert_eq!(
+            file.repositories
+                .iter()
+                .find(|entry| entry.name == "meta-foo")
+                .unwrap()
+                .tags,
+            vec!["vendor"]
+        );
+
+        let temp = NamedTempFile::new()?;
+        ScanFile::parse("/work/meta-foo\n")?.write(temp.path())?;
+        assert!(refresh_file(temp.path()).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn scan_path_parsing_test() -> Result<()> {
+        let scan_path = |path: &str, min_depth, max_depth| ScanPath {
+            path: PathBuf::from(path),
+            min_depth,
+            max_depth,
+        };
+
+        assert_eq!("~/src".parse::<ScanPath>()?, scan_path("~/src", None, None));
+        assert_eq!(
+            "~/src:2".parse::<ScanPath>()?,
+            scan_path("~/src", None, Some(2))
+        );
+        assert_eq!(
+            "/opt/bsp:1..4".parse::<ScanPath>()?,
+            scan_path("/opt/bsp", Some(1), Some(4))
+        );
+        assert_eq!(
+            "/mnt/a:b".parse::<ScanPath>()?,
+            scan_path("/mnt/a:b", None, None)
+        );
+        assert_eq!(
+            "dir:1..x".parse::<ScanPath>()?,
+            scan_path("dir:1..x", None, None)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn scan_roots_with_own_depths_and_base() -> Result<()> {
+        let workspace = TempDir::new()?;
+        let outside = TempDir::new()?;
+        let src = workspace.path().join("src");
+        let bsp = workspace.path().join("bsp");
+
+        let _ = Repository::init(src.join("tools"))?;
+        let _ = Repository::init(src.join("deep").join("lib"))?;
+        let _ = Repository::init(bsp.join("layers").join("meta-foo"))?;
+        let _ = Repository::init(outside.path().join("poky"))?;
+
+        let root = |path: &Path, min_depth, max_depth| ScanRoot {
+            path: PathBuf::from(path),
+            settings: ScanSettings {
+                min_depth,
+                max_depth,
+                relative: true,
+                base: Some(PathBuf::from(workspace.path())),
+                ..Default::default()
+            },
+        };
+
+        let file = scan_roots(&[
+            root(&src, 0, 1),
+            root(&bsp, 2, 2),
+            root(outside.path(), 0, 1),
+            root(&src, 0, 1),
+        ])?;
+
+        assert_eq!(file.roots.len(), 3);
+        assert_eq!(
+            sorted_paths(file.repositories.clone()),
+            vec![
+                outside.path().join("poky"),
+                PathBuf::from("bsp/layers/meta-foo"),
+                PathBuf::from("src/tools"),
+            ]
+        );
+        assert_eq!(
+            file.resolve(
+                file.repositories
+                    .iter()
+                    .find(|entry| entry.name == "tools")
+                    .unwrap()
+            ),
+            workspace.path().join("src/tools")
+        );
+
+        assert!(scan_roots(&[]).is_err());
 
         Ok(())
     }
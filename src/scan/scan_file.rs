@@ -0,0 +1,645 @@
+use anyhow::{Context, Result};
+use chrono::Local;
+use log::debug;
+use serde::{Deserialize, Serialize};
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::exec::report::RepoKind;
+
+/// Version of the scan file format written by this version of rgit
+pub const SCAN_FILE_VERSION: u32 = 1;
+
+/// Describes how directories are scanned for repositories
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct ScanSettings {
+    /// Level of directories on which scan starts, 0 is the root itself
+    pub min_depth: usize,
+    /// Level of directories on which scan stops
+    pub max_depth: usize,
+    /// Paths of found repositories are relative to `base`
+    pub relative: bool,
+    /// Directory relative paths are computed against, the root if not given
+    pub base: Option<PathBuf>,
+    /// Repositories are looked for also inside found repositories
+    pub nested: bool,
+    /// Initialized submodules of found repositories are listed as well
+    pub submodules: bool,
+    /// Linked worktrees of found repositories are listed as well, even outside of the root
+    pub worktrees: bool,
+    /// Globs of skipped directories in gitignore syntax
+    pub excludes: Vec<String>,
+}
+
+impl Default for ScanSettings {
+    fn default() -> Self {
+        ScanSettings {
+            min_depth: 0,
+            max_depth: 3,
+            relative: false,
+            base: None,
+            nested: false,
+            submodules: false,
+            worktrees: false,
+            excludes: Vec::new(),
+        }
+    }
+}
+
+impl ScanSettings {
+    /// Returns directory paths of repositories found in `root` are relative to, `None` for
+    /// absolute paths
+    pub fn relative_to<'a>(&'a self, root: &'a Path) -> Option<&'a Path> {
+        match self.relative {
+            true => Some(self.base.as_deref().unwrap_or(root)),
+            false => None,
+        }
+    }
+}
+
+/// Directory scanned for repositories together with settings of the scan
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ScanRoot {
+    /// Scanned directory
+    pub path: PathBuf,
+    /// Settings of the scan
+    #[serde(flatten)]
+    pub settings: ScanSettings,
+}
+
+impl ScanRoot {
+    /// Creates root scanned with default settings
+    pub fn new(path: &Path) -> ScanRoot {
+        ScanRoot {
+            path: PathBuf::from(path),
+            settings: ScanSettings::default(),
+        }
+    }
+}
+
+/// Repository found by scan
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ScanEntry {
+    /// Path to the repository
+    pub path: PathBuf,
+    /// Index of the scanned directory in `roots` the repository was found in, relative paths are
+    /// relative to its base
+    #[serde(default)]
+    pub root: Option<usize>,
+    /// Name of the repository, i.e. name of its directory
+    pub name: String,
+    /// Path to the superproject if the repository is its submodule
+    #[serde(default)]
+    pub parent: Option<PathBuf>,
+    /// Kind of the repository, e.g. bare repository
+    #[serde(default)]
+    pub kind: RepoKind,
+    /// URL of the default remote, `origin` or the first configured remote
+    #[serde(default)]
+    pub remote: Option<String>,
+    /// Branch checked out at the time of the scan, `None` if HEAD was detached
+    #[serde(default)]
+    pub branch: Option<String>,
+    /// Groups the repository was added to by the user
+    #[serde(default)]
+    pub groups: Vec<String>,
+    /// Tags assigned by the user
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+/// Kind of labels assigned to repositories by the user
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Label {
+    Group,
+    Tag,
+}
+
+impl std::fmt::Display for Label {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Label::Group => write!(f, "group"),
+            Label::Tag => write!(f, "tag"),
+        }
+    }
+}
+
+impl ScanEntry {
+    /// Creates entry of repository at `path` without any metadata
+    pub fn new(path: &Path) -> ScanEntry {
+        let name = path
+            .canonicalize()
+            .unwrap_or_else(|_| PathBuf::from(path))
+            .file_name()
+            .map_or_else(
+                || path.display().to_string(),
+                |name| name.to_string_lossy().to_string(),
+            );
+
+        ScanEntry {
+            path: PathBuf::from(path),
+            root: None,
+            name,
+            parent: None,
+            kind: RepoKind::Normal,
+            remote: None,
+            branch: None,
+            groups: Vec::new(),
+            tags: Vec::new(),
+        }
+    }
+
+    /// Returns groups or tags of the repository
+    pub fn labels(&self, label: Label) -> &[String] {
+        match label {
+            Label::Group => &self.groups,
+            Label::Tag => &self.tags,
+        }
+    }
+
+    fn labels_mut(&mut self, label: Label) -> &mut Vec<String> {
+        match label {
+            Label::Group => &mut self.groups,
+            Label::Tag => &mut self.tags,
+        }
+    }
+
+    /// Checks if the repository is the one given by the user as its name or path
+    fn is_named(&self, repo: &str) -> bool {
+        self.name == repo || self.path == Path::new(repo)
+    }
+}
+
+/// Results of scan saved in a file, `~/.rgit` by default
+///
+/// Files are saved as JSON, files with a newline separated list of paths written by older versions
+/// of rgit are read as well.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ScanFile {
+    /// Version of the format
+    pub version: u32,
+    /// Scanned directories, empty for files in legacy format
+    #[serde(default)]
+    pub roots: Vec<ScanRoot>,
+    /// Time of the last scan as a unix timestamp, `None` for files in legacy format
+    pub timestamp: Option<i64>,
+    /// Found repositories
+    pub repositories: Vec<ScanEntry>,
+}
+
+impl ScanFile {
+    /// Creates file with results of scanning `root` now
+    ///
+    /// # Arguments
+    ///
+    /// * `root` - scanned directory
+    /// * `repositories` - found repositories
+    pub fn new(root: ScanRoot, repositories: Vec<ScanEntry>) -> ScanFile {
+        ScanFile {
+            version: SCAN_FILE_VERSION,
+            roots: vec![root],
+            timestamp: Some(Local::now().timestamp()),
+            repositories: repositories
+                .into_iter()
+                .map(|entry| ScanEntry {
+                    root: Some(0),
+                    ..entry
+                })
+                .collect(),
+        }
+    }
+
+    /// Reads scan results from the file in current or legacy format
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - path to the file
+    pub fn read(path: &Path) -> Result<ScanFile> {
+        debug!("Reading scan results from file {}", path.display());
+
+        let content = std::fs::read_to_string(path)
+            .context(format!("Failed to read file {}", path.display()))?;
+
+        ScanFile::parse(&content).context(format!("Failed to parse file {}", path.display()))
+    }
+
+    /// Parses scan results in current or legacy format
+    ///
+    /// # Arguments
+    ///
+    /// * `content` - content of the file
+    pub fn parse(content: &str) -> Result<ScanFile> {
+        if !content.trim_start().starts_with('{') {
+            return Ok(ScanFile {
+                version: 0,
+                roots: Vec::new(),
+                timestamp: None,
+                repositories: content
+                    .lines()
+                    .map(str::trim)
+                    .filter(|line| !line.is_empty())
+                    .map(|line| ScanEntry::new(Path::new(line)))
+                    .collect(),
+            });
+        }
+
+        let file: ScanFile = serde_json::from_str(content).context("Invalid scan results")?;
+
+        if file.version > SCAN_FILE_VERSION {
+            anyhow::bail!(
+                "Unsupported version {} of scan results, supported version: {}",
+                file.version,
+                SCAN_FILE_VERSION
+            );
+        }
+
+        Ok(file)
+    }
+
+    /// Assigns the group or tag to repositories
+    ///
+    /// Fails without changing anything if any of the repositories isn't in the file.
+    ///
+    /// # Arguments
+    ///
+    /// * `label` - kind of the label
+    /// * `name` - name of the group or tag
+    /// * `repos` - names or paths of repositories, all repositories with the same name are labeled
+    pub fn add_label(&mut self, label: Label, name: &str, repos: &[String]) -> Result<()> {
+        if let Some(repo) = repos
+            .iter()
+            .find(|repo| !self.repositories.iter().any(|entry| entry.is_named(repo)))
+        {
+            anyhow::bail!("Repository {} not found in scan results", repo);
+        }
+
+        for entry in self
+            .repositories
+            .iter_mut()
+            .filter(|entry| repos.iter().any(|repo| entry.is_named(repo)))
+        {
+            let labels = entry.labels_mut(label);
+            if !labels.iter().any(|l| l == name) {
+                labels.push(String::from(name));
+                labels.sort();
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Removes the group or tag from repositories
+    ///
+    /// # Arguments
+    ///
+    /// * `label` - kind of the label
+    /// * `name` - name of the group or tag
+    /// * `repos` - names or paths of repositories, the label is removed from all repositories if
+    ///   empty
+    pub fn remove_label(&mut self, label: Label, name: &str, repos: &[String]) -> Result<()> {
+        let mut removed = false;
+
+        for entry in self
+            .repositories
+            .iter_mut()
+            .filter(|entry| repos.is_empty() || repos.iter().any(|repo| entry.is_named(repo)))
+        {
+            let labels = entry.labels_mut(label);
+            let count = labels.len();
+            labels.retain(|l| l != name);
+            removed |= labels.len() != count;
+        }
+
+        if !removed {
+            anyhow::bail!("No repository with {} {} found", label, name);
+        }
+
+        Ok(())
+    }
+
+    /// Returns groups or tags with repositories they're assigned to, sorted by name
+    ///
+    /// # Arguments
+    ///
+    /// * `label` - kind of the label
+    pub fn labels(&self, label: Label) -> BTreeMap<&str, Vec<&ScanEntry>> {
+        let mut labels: BTreeMap<&str, Vec<&ScanEntry>> = BTreeMap::new();
+
+        for entry in &self.repositories {
+            for name in entry.labels(label) {
+                labels.entry(name.as_str()).or_default().push(entry);
+            }
+        }
+
+        labels
+    }
+
+    /// Adds results of another scan, repositories found again replace the old entries but keep
+    /// their groups and tags
+    ///
+    /// Repositories are compared by their resolved paths, so the same relative path computed
+    /// against different bases belongs to different repositories.
+    ///
+    /// # Arguments
+    ///
+    /// * `other` - results of the new scan
+    pub fn merge(&mut self, other: ScanFile) {
+        let mut resolved: Vec<PathBuf> = self
+            .repositories
+            .iter()
+            .map(|entry| self.resolve(entry))
+            .collect();
+        let other_resolved: Vec<PathBuf> = other
+            .repositories
+            .iter()
+            .map(|entry| other.resolve(entry))
+            .collect();
+
+        let roots: Vec<usize> = other
+            .roots
+            .into_iter()
+            .map(|root| self.add_root(root))
+            .collect();
+
+        for (entry, path) in other.repositories.into_iter().zip(other_resolved) {
+            let entry = ScanEntry {
+                root: entry.root.and_then(|index| roots.get(index).copied()),
+                ..entry
+            };
+
+            match resolved.iter().position(|old| is_same_path(old, &path)) {
+                Some(index) => {
+                    let old = &mut self.repositories[index];
+                    let groups = std::mem::take(&mut old.groups);
+                    let tags = std::mem::take(&mut old.tags);
+                    *old = ScanEntry {
+                        groups,
+                        tags,
+                        ..entry
+                    };
+                }
+                None => {
+                    self.repositories.push(entry);
+                    resolved.push(path);
+                }
+            }
+        }
+
+        self.version = SCAN_FILE_VERSION;
+        self.timestamp = other.timestamp;
+    }
+
+    /// Copies groups and tags from previous scan results to repositories with the same resolved
+    /// path
+    ///
+    /// # Arguments
+    ///
+    /// * `previous` - previous content of the file
+    pub fn keep_labels(&mut self, previous: &ScanFile) {
+        let resolved: Vec<PathBuf> = self
+            .repositories
+            .iter()
+            .map(|entry| self.resolve(entry))
+            .collect();
+
+        for (entry, path) in self.repositories.iter_mut().zip(resolved) {
+            if let Some(old) = previous
+                .repositories
+                .iter()
+                .find(|old| is_same_path(&previous.resolve(old), &path))
+            {
+                entry.groups = old.groups.clone();
+                entry.tags = old.tags.clone();
+            }
+        }
+    }
+
+    /// Returns path under which the repository can be opened
+    ///
+    /// Relative paths are joined to the base of the directory the repository was found in, e.g.
+    /// `--base` of the scan. Paths of repositories not found by a recorded scan are returned as
+    /// they are.
+    ///
+    /// # Arguments
+    ///
+    /// * `entry` - repository saved in the file
+    pub fn resolve(&self, entry: &ScanEntry) -> PathBuf {
+        entry
+            .root
+            .and_then(|index| self.roots.get(index))
+            .and_then(|root| root.settings.relative_to(&root.path))
+            .map_or_else(|| PathBuf::from(&entry.path), |base| base.join(&entry.path))
+    }
+
+    /// Removes repositories which resolved paths don't satisfy `predicate` and returns them
+    ///
+    /// # Arguments
+    ///
+    /// * `predicate` - called with resolved path of every repository
+    pub fn retain<P>(&mut self, mut predicate: P) -> Vec<ScanEntry>
+    where
+        P: FnMut(&Path) -> bool,
+    {
+        let (kept, removed) = std::mem::take(&mut self.repositories)
+            .into_iter()
+            .partition(|entry| predicate(&self.resolve(entry)));
+
+        self.repositories = kept;
+
+        removed
+    }
+
+    /// Records the scanned directory and returns its index in `roots`
+    ///
+    /// A directory scanned again with the same base replaces the old record, so relative paths
+    /// of its repositories keep pointing at the same directories.
+    fn add_root(&mut self, root: ScanRoot) -> usize {
+        let index = self.roots.iter().position(|old| {
+            is_same_path(&old.path, &root.path)
+                && old.settings.relative_to(&old.path) == root.settings.relative_to(&root.path)
+        });
+
+        match index {
+            Some(index) => {
+                self.roots[index] = root;
+                index
+            }
+            None => {
+                self.roots.push(root);
+                self.roots.len() - 1
+            }
+        }
+    }
+
+    /// Saves scan results to the file
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - path to the file, it's overwritten if it exists
+    pub fn write(&self, path: &Path) -> Result<()> {
+        debug!("Saving scan results to file {}", path.display());
+
+        let content =
+            serde_json::to_string_pretty(self).context("Failed to serialize scan results")?;
+
+        std::fs::write(path, content + "\n")
+            .context(format!("Failed to write file {}", path.display()))
+    }
+}
+
+/// Checks if both paths point at the same directory, comparing them literally if they don't exist
+fn is_same_path(a: &Path, b: &Path) -> bool {
+    a == b
+        || matches!(
+            (a.canonicalize(), b.canonicalize()),
+            (Ok(a), Ok(b)) if a == b
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn scan_file_round_trip() -> Result<()> {
+        let mut entry = ScanEntry::new(Path::new("/work/meta-foo"));
+        entry.remote = Some(String::from("https://example.com/meta-foo.git"));
+        entry.branch = Some(String::from("master"));
+        entry.tags = vec![String::from("bsp")];
+
+        let file = ScanFile::new(ScanRoot::new(Path::new("/work")), vec![entry]);
+        let temp = NamedTempFile::new()?;
+        file.write(temp.path())?;
+
+        let read = ScanFile::read(temp.path())?;
+        assert_eq!(read, file);
+        assert_eq!(read.repositories[0].name, "meta-foo");
+
+        Ok(())
+    }
+
+    #[test]
+    fn scan_file_reads_legacy_format() -> Result<()> {
+        let file = ScanFile::parse("/work/meta-foo\n\nmeta-bar\n")?;
+
+        assert_eq!(file.version, 0);
+        assert!(file.roots.is_empty());
+        assert_eq!(
+            file.repositories,
+            vec![
+                ScanEntry::new(Path::new("/work/meta-foo")),
+                ScanEntry::new(Path::new("meta-bar")),
+            ]
+        );
+        assert_eq!(file.repositories[1].name, "meta-bar");
+
+        Ok(())
+    }
+
+    #[test]
+    fn scan_file_defaults_missing_metadata() -> Result<()> {
+        let file = ScanFile::parse(
+            r#"{"version": 1, "roots": [{"path": "/work"}], "timestamp": null, "repositories": [{"path": "a", "name": "a"}]}"#,
+        )?;
+        assert_eq!(file.repositories[0].kind, RepoKind::Normal);
+        assert!(file.repositories[0].tags.is_empty());
+        assert_eq!(file.roots, vec![ScanRoot::new(Path::new("/work"))]);
+        assert_eq!(file.roots[0].settings.max_depth, 3);
+
+        Ok(())
+    }
+
+    #[test]
+    fn scan_file_rejects_newer_versions() {
+        assert!(ScanFile::parse(
+            r#"{"version": 2, "roots": [], "timestamp": null, "repositories": []}"#
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn add_and_remove_labels() -> Result<()> {
+        let mut file = ScanFile::new(
+            ScanRoot::new(Path::new("/work")),
+            vec![
+                ScanEntry::new(Path::new("/work/meta-foo")),
+                ScanEntry::new(Path::new("/work/meta-bar")),
+                ScanEntry::new(Path::new("/work/tools")),
+            ],
+        );
+
+        let repos = |names: &[&str]| names.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+
+        file.add_label(Label::Group, "bsp", &repos(&["meta-foo", "/work/meta-bar"]))?;
+        file.add_label(Label::Group, "bsp", &repos(&["meta-foo"]))?;
+        file.add_label(Label::Tag, "vendor", &repos(&["meta-bar"]))?;
+        assert!(file
+            .add_label(Label::Group, "bsp", &repos(&["tools", "missing"]))
+            .is_err());
+
+        assert_eq!(file.repositories[0].groups, vec!["bsp"]);
+        assert!(file.repositories[2].groups.is_empty());
+
+        let groups = file.labels(Label::Group);
+        assert_eq!(groups.keys().collect::<Vec<_>>(), vec![&"bsp"]);
+        assert_eq!(groups["bsp"].len(), 2);
+
+        file.remove_label(Label::Group, "bsp", &repos(&["meta-foo"]))?;
+        assert_eq!(file.labels(Label::Group)["bsp"][0].name, "meta-bar");
+
+        file.remove_label(Label::Group, "bsp", &[])?;
+        assert!(file.labels(Label::Group).is_empty());
+        assert!(file.remove_label(Label::Group, "bsp", &[]).is_err());
+        assert_eq!(file.repositories[1].tags, vec!["vendor"]);
+
+        let mut rescanned = ScanFile::new(
+            ScanRoot::new(Path::new("/work")),
+            vec![ScanEntry::new(Path::new("/work/meta-bar"))],
+        );
+        rescanned.keep_labels(&file);
+        assert_eq!(rescanned.repositories[0].tags, vec!["vendor"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn merge_and_retain_repositories() -> Result<()> {
+        let mut file = ScanFile::new(
+            ScanRoot::new(Path::new("/work/a")),
+            vec![
+                ScanEntry::new(Path::new("/work/a/meta-foo")),
+                ScanEntry::new(Path::new("/work/a/meta-bar")),
+            ],
+        );
+        file.repositories[0].groups = vec![String::from("bsp")];
+
+        let mut rescanned = ScanEntry::new(Path::new("/work/a/meta-foo"));
+        rescanned.branch = Some(String::from("release"));
+
+        let mut root = ScanRoot::new(Path::new("/work/a"));
+        root.settings.max_depth = 5;
+
+        file.merge(ScanFile::new(root, vec![rescanned]));
+        file.merge(ScanFile::new(
+            ScanRoot::new(Path::new("/work/b")),
+            vec![ScanEntry::new(Path::new("/work/b/tools"))],
+        ));
+
+        assert_eq!(file.roots.len(), 2);
+        assert_eq!(file.roots[0].settings.max_depth, 5);
+        assert_eq!(file.repositories.len(), 3);
+        assert_eq!(file.repositories[0].branch, Some(String::from("release")));
+        assert_eq!(file.repositories[0].groups, vec!["bsp"]);
+        assert_eq!(file.repositories[2].root, Some(1));
+
+        let removed = file.retain(|path| !path.ends_with("meta-bar"));
+        assert_eq!(removed.len(), 1);
+        assert_eq!(removed[0].path, PathBuf::from("/work/a/meta-bar"));
+        assert_eq!(file.repositories.len(), 2);
+
+        Ok(())
+    }
+}
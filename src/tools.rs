@@ -1,4 +1,6 @@
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
+use chrono::{Duration, Local, NaiveDate, NaiveDateTime, TimeZone};
+use std::convert::TryFrom;
 use std::path::PathBuf;
 use std::process::Command;
 
@@ -11,13 +13,210 @@ pub fn get_default_scan_path() -> Result<PathBuf> {
 
 /// Returns current git username
 pub fn get_git_user_name() -> Result<String> {
-    let user = Command::new("git")
+    get_git_config("user.name")
+}
+
+/// Returns current git user email
+pub fn get_git_user_email() -> Result<String> {
+    get_git_config("user.email")
+}
+
+/// Returns value of git config `key`, empty if it's not set
+fn get_git_config(key: &str) -> Result<String> {
+    let value = Command::new("git")
         .arg("config")
-        .arg("user.name")
+        .arg(key)
         .output()
-        .context("Failed to execute: git config user.name")?;
+        .context(format!("Failed to execute: git config {}", key))?;
+
+    let value = String::from_utf8(value.stdout)?;
+
+    Ok(String::from(value.trim()))
+}
+
+/// Parses date given on the command line and returns it as a unix timestamp
+///
+/// Accepts ISO dates (`2026-10-01`, `2026-10-01 12:30`, `2026-10-01T12:30:00`, RFC 3339),
+/// `now`, `today`, `yesterday` and relative forms like `2 weeks ago` or `3.days.ago`.
+/// Dates without a time zone are interpreted in local time.
+///
+/// # Arguments
+///
+/// * `date` - date to parse
+pub fn parse_date(date: &str) -> Result<i64> {
+    let date = date.trim();
+    let now = Local::now();
+
+    match date {
+        "now" => return Ok(now.timestamp()),
+        "today" => return local_midnight(now.date().naive_local()),
+        "yesterday" => return local_midnight(now.date().naive_local().pred()),
+        _ => {}
+    }
+
+    if let Ok(datetime) = chrono::DateTime::parse_from_rfc3339(date) {
+        return Ok(datetime.timestamp());
+    }
+
+    for format in &["%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M"] {
+        if let Ok(datetime) = NaiveDateTime::parse_from_str(date, format) {
+            return local_timestamp(&datetime);
+        }
+    }
+
+    if let Ok(day) = NaiveDate::parse_from_str(date, "%Y-%m-%d") {
+        return local_midnight(day);
+    }
+
+    let words: Vec<&str> = date
+        .split(|c: char| c.is_whitespace() || c == '.')
+        .filter(|word| !word.is_empty())
+        .collect();
+
+    if let [count, unit, "ago"] = words.as_slice() {
+        let count: u64 = count
+            .parse()
+            .context(format!("Invalid number in date: {}", date))?;
+        let count =
+            i32::try_from(count).context(format!("Number out of range in date: {}", date))?;
+
+        let unit = match unit.trim_end_matches('s') {
+            "second" => Duration::seconds(1),
+            "minute" => Duration::minutes(1),
+            "hour" => Duration::hours(1),
+            "day" => Duration::days(1),
+            "week" => Duration::weeks(1),
+            "month" => Duration::days(30),
+            "year" => Duration::days(365),
+            _ => bail!("Unknown time unit in date: {}", date),
+        };
+
+        // chrono's `Duration` has no checked multiplication and panics when out of its range
+        let seconds = unit
+            .num_seconds()
+            .checked_mul(i64::from(count))
+            .filter(|seconds| *seconds <= Duration::max_value().num_seconds())
+            .context(format!("Date out of range: {}", date))?;
+
+        return Ok(now
+            .checked_sub_signed(Duration::seconds(seconds))
+            .context(format!("Date out of range: {}", date))?
+            .timestamp());
+    }
+
+    bail!("Failed to parse date: {}", date)
+}
+
+/// Formats time relative to `now` the way `git log` does it for `%cr`, e.g. `3 days ago`
+///
+/// # Arguments
+///
+/// * `timestamp` - unix timestamp to format
+/// * `now` - current unix timestamp
+pub fn format_relative_time(timestamp: i64, now: i64) -> String {
+    let seconds = now - timestamp;
+
+    if seconds < 0 {
+        return String::from("in the future");
+    }
+
+    let minutes = (seconds + 30) / 60;
+    let hours = (minutes + 30) / 60;
+    let days = (hours + 12) / 24;
+
+    let (count, unit) = if seconds < 90 {
+        (seconds, "second")
+    } else if minutes < 90 {
+        (minutes, "minute")
+    } else if hours < 36 {
+        (hours, "hour")
+    } else if days < 14 {
+        (days, "day")
+    } else if days < 70 {
+        ((days + 3) / 7, "week")
+    } else if days < 365 {
+        ((days + 15) / 30, "month")
+    } else {
+        ((days + 183) / 365, "year")
+    };
+
+    match count {
+        1 => format!("1 {} ago", unit),
+        _ => format!("{} {}s ago", count, unit),
+    }
+}
+
+fn local_midnight(day: NaiveDate) -> Result<i64> {
+    local_timestamp(&day.and_hms(0, 0, 0))
+}
+
+fn local_timestamp(datetime: &NaiveDateTime) -> Result<i64> {
+    Ok(Local
+        .from_local_datetime(datetime)
+        .earliest()
+        .context(format!("Invalid local time: {}", datetime))?
+        .timestamp())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_date_iso() -> Result<()> {
+        let expected = Local.ymd(2026, 10, 1).and_hms(0, 0, 0).timestamp();
+
+        assert_eq!(parse_date("2026-10-01")?, expected);
+        assert_eq!(parse_date("2026-10-01 00:00")?, expected);
+        assert_eq!(parse_date("2026-10-01T00:00:00")?, expected);
+        assert_eq!(parse_date("2026-10-01T00:00:00+00:00")?, 1_790_812_800);
+
+        Ok(())
+    }
+
+    #[test]
+    fn parse_date_relative() -> Result<()> {
+        let now = Local::now().timestamp();
+        let two_weeks = 14 * 24 * 60 * 60;
+
+        assert!((now - two_weeks - parse_date("2 weeks ago")?).abs() <= 1);
+        assert!((now - two_weeks - parse_date("2.weeks.ago")?).abs() <= 1);
+        assert!((now - 60 - parse_date("1 minute ago")?).abs() <= 1);
+        assert_eq!(
+            parse_date("yesterday")?,
+            local_timestamp(&Local::today().naive_local().pred().and_hms(0, 0, 0))?
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn format_relative_time_units() {
+        let now = 1_000_000_000;
+
+        assert_eq!(format_relative_time(now - 1, now), "1 second ago");
+        assert_eq!(format_relative_time(now - 45, now), "45 seconds ago");
+        assert_eq!(format_relative_time(now - 5 * 60, now), "5 minutes ago");
+        assert_eq!(format_relative_time(now - 3 * 60 * 60, now), "3 hours ago");
+        assert_eq!(format_relative_time(now - 2 * 86400, now), "2 days ago");
+        assert_eq!(format_relative_time(now - 21 * 86400, now), "3 weeks ago");
+        assert_eq!(format_relative_time(now - 120 * 86400, now), "4 months ago");
+        assert_eq!(format_relative_time(now - 800 * 86400, now), "2 years ago");
+        assert_eq!(format_relative_time(now + 10, now), "in the future");
+    }
 
-    let user = String::from_utf8(user.stdout)?;
+    #[test]
+    fn parse_date_invalid() {
+        assert!(parse_date("someday").is_err());
+        assert!(parse_date("2 fortnights ago").is_err());
+        assert!(parse_date("two weeks ago").is_err());
+    }
 
-    Ok(String::from(user.trim()))
+    #[test]
+    fn parse_date_out_of_range() {
+        assert!(parse_date("1000000 years ago").is_err());
+        assert!(parse_date("3000000000 days ago").is_err());
+        assert!(parse_date("99999999999 weeks ago").is_err());
+        assert!(parse_date("-1 days ago").is_err());
+    }
 }